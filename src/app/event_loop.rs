@@ -1,24 +1,27 @@
 use std::sync::mpsc;
-use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use failure::Error;
-use termion::{
-    async_stdin,
-    event::{Event, Key, MouseButton, MouseEvent},
-    input::TermRead,
-};
+use signal_hook::consts::{SIGCONT, SIGHUP, SIGINT, SIGTERM, SIGTSTP, SIGWINCH};
+use termion::event::{Event, Key, MouseButton, MouseEvent};
 
 use crate::{
-    app::{settings::Settings, window::WindowAdjust},
+    app::{
+        ingest::{IngestQueue, QUEUE_SIZE},
+        reactor::Reactor,
+        settings::Settings,
+        window::{RowsAdjust, WindowAdjust},
+    },
     data::{
+        diagnostics::Report,
         fetcher_loop::{FetcherError, FetcherLoop},
         series::{SeriesSet, Slice},
         state::State,
     },
     ui::{
+        gutter::gutter_width,
         surface::{Surface, TermSurface},
-        ui_init,
+        ui_init::{self, TermModes},
     },
 };
 
@@ -29,31 +32,71 @@ pub enum Message {
     DataSlice(Slice),
     ExtendDataSet(SeriesSet),
     FetchError(FetcherError),
+    Diagnostics(Report),
+}
+
+// What main loop does after handling a message.
+enum Next {
+    Render,
+    Wait,
+    Suspend,
+    Quit,
+}
+
+// Gives the terminal back to the shell and stops the process, the way
+// default SIGTSTP handler would. Once continued, terminal is taken back
+// and redrawn from scratch.
+fn suspend(modes: &TermModes, surface: &mut impl Surface) -> Result<(), Error> {
+    modes.suspend()?;
+    unsafe {
+        libc::raise(libc::SIGSTOP);
+    }
+    modes.resume()?;
+    surface.clear()?;
+    Ok(())
 }
 
 // Main event loop.
 pub struct EventLoop {
-    sender: mpsc::Sender<Message>,
     receiver: mpsc::Receiver<Message>,
     state: State,
     fetcher_loop: FetcherLoop,
 }
 
 impl EventLoop {
+    // width available for the charts.
+    fn width(&self, surface: &impl Surface) -> Result<i64, Error> {
+        let gutter = gutter_width(self.state.is_compact()) as i64;
+        Ok((surface.columns()? - gutter).max(1))
+    }
+
     fn on_mouse_press(&mut self, b: MouseButton, x: i64, w: i64, h: i64) -> bool {
+        let heights = self.state.heights();
         let mut xm = WindowAdjust::new(self.state.data.series_size(), w, &mut self.state.x);
-        let mut ym = WindowAdjust::new(self.state.data.series_count(), h, &mut self.state.y);
+        let mut ym = RowsAdjust::new(heights, h, &mut self.state.y);
         match b {
             MouseButton::WheelDown => ym.move_offset(1),
             MouseButton::WheelUp => ym.move_offset(-1),
-            MouseButton::Left => xm.set_cursor(x - 1),
+            MouseButton::Left => xm.set_cursor(x),
             _ => false,
         }
     }
 
+    // keeps the same data under the cursor with the new screen size.
+    fn on_resize(&mut self, w: i64, h: i64) -> bool {
+        let auto = self.state.is_auto();
+        let mut x = WindowAdjust::new(self.state.data.series_size(), w, &mut self.state.x);
+        x.on_resize();
+        if auto {
+            x.end();
+        }
+        RowsAdjust::new(self.state.heights(), h, &mut self.state.y).clamp_offset()
+    }
+
     fn on_key_press(&mut self, input: Key, w: i64, h: i64) -> bool {
+        let heights = self.state.heights();
         let mut x = WindowAdjust::new(self.state.data.series_size(), w, &mut self.state.x);
-        let mut y = WindowAdjust::new(self.state.data.series_count(), h, &mut self.state.y);
+        let mut y = RowsAdjust::new(heights, h, &mut self.state.y);
 
         match input {
             // vertical navigation
@@ -75,110 +118,217 @@ impl EventLoop {
             Key::Char('H') => x.cursor_begin(),
             Key::Char('L') => x.cursor_end(),
 
-            Key::Char('$') => (x.end() || x.cursor_end()),
-            Key::Char('0') => (x.begin() || x.cursor_begin()),
+            Key::Char('$') => x.end() || x.cursor_end(),
+            Key::Char('0') => x.begin() || x.cursor_begin(),
+
+            // time-based navigation
+            Key::Char(']') | Key::Char('[') => {
+                let cursor = x.cursor_position();
+                let jump = if input == Key::Char(']') {
+                    self.state.jump
+                } else {
+                    -self.state.jump
+                };
+                match self.state.data.index_after(cursor as usize, jump) {
+                    Some(i) => x.move_cursor(i as i64 - cursor),
+                    None => false,
+                }
+            }
 
             Key::Char('c') => self.state.hide_cursor(),
+            Key::Char('e') => self.state.toggle_diagnostics(),
 
             Key::Char('p') => {
-                // TODO: this can race. We shall update UI after fetcher 'acknowledged' pause
                 self.fetcher_loop.pause();
-                self.state.pause()
+                self.state.pause(w)
             }
             _ => false,
         }
     }
 
-    // Entry point to main event loop.
-    pub fn start(settings: Settings) -> Result<(), Error> {
-        let mut terminal = ui_init::init()?;
+    // Entry point to main event loop. Returns exit status: 0 when user
+    // quits, 128 + signal number when terminated by a signal. Terminal is
+    // restored once the status is returned.
+    pub fn start(settings: Settings) -> Result<i32, Error> {
+        let (mut terminal, modes) = ui_init::init(settings.palette.depth)?;
         // surface is an entity which is aware of layout, thus,
         // surface can provide information on the screen capacity.
-        let mut surface = TermSurface::new(&mut terminal);
+        let mut surface = TermSurface::new(
+            &mut terminal,
+            settings.theme.clone(),
+            settings.palette.clone(),
+            settings.glyphs,
+        );
 
-        // queue for main event loop
-        let (sender, receiver) = mpsc::channel();
+        // everything main loop reacts to is delivered through the reactor.
+        let mut reactor = Reactor::new()?;
+        for signal in &[SIGWINCH, SIGINT, SIGTERM, SIGHUP, SIGTSTP, SIGCONT] {
+            reactor.watch(*signal)?;
+        }
 
-        let fetcher_loop = FetcherLoop::new(sender.clone(), &settings);
+        // queue for main event loop; bounded, so that a fast producer
+        // can't make it grow without limits.
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let queue = IngestQueue::new(sender, settings.overflow, reactor.waker());
+        let ingest = queue.status();
+
+        let fetcher_loop = FetcherLoop::new(queue, &settings);
 
         let mut event_loop = EventLoop {
-            sender,
             receiver,
             state: State::from_settings(&settings),
             fetcher_loop,
         };
 
-        event_loop.add(move |sender: mpsc::Sender<Message>| -> Result<(), Error> {
-            let mut events = async_stdin().events();
-            loop {
-                match events.next() {
-                    Some(Ok(Event::Key(k))) => sender.send(Message::KeyPress(k))?,
-                    Some(Ok(Event::Mouse(MouseEvent::Press(button, a, _)))) => {
-                        sender.send(Message::MousePress((button, a)))?
-                    }
-                    _ => {}
-                }
-                thread::sleep(Duration::from_millis(50));
-            }
-        });
-
         event_loop.fetcher_loop.fetch();
 
-        // main event loop
+        // main event loop. Messages are applied as they come, but
+        // the screen is redrawn at most once per frame interval.
+        let frame = Duration::from_secs(1) / settings.fps;
+        let mut last_render: Option<Instant> = None;
+        let mut dirty = false;
+        // messages taken from the queue so far.
+        let mut received = 0;
         loop {
-            match event_loop.receiver.recv()? {
-                // Append new column, with potentially new column names.
-                // series might be reordered as a result of this operation.
-                Message::ExtendDataSet(d) => {
-                    event_loop.state.extend_dataset(d, surface.width()?);
-                    surface.render(&event_loop.state)?;
-                }
-                // Append new slice to the existing set of columns.
-                Message::DataSlice(s) => {
-                    event_loop.state.append_slice(s, surface.width()?);
-                    surface.render(&event_loop.state)?;
-                }
-                // Handle fetching error.
-                Message::FetchError(e) => {
-                    // error will be cleared on next successful data fetch
-                    event_loop.state.on_error(format!("{}", e));
-                    // we need to render to show 'error' to user.
-                    surface.render(&event_loop.state)?;
-                }
-                // mouse event; includes both press/scroll.
-                Message::MousePress((b, x)) => {
-                    if event_loop.on_mouse_press(b, x as i64, surface.width()?, surface.height()?) {
-                        surface.render(&event_loop.state)?;
+            // without anything to draw, wait for input indefinitely.
+            let deadline = if dirty {
+                Some(last_render.map_or_else(Instant::now, |t| t + frame))
+            } else {
+                None
+            };
+            let ready = reactor.wait(deadline)?;
+
+            if let Some(signal) = ready
+                .signals
+                .iter()
+                .find(|s| [SIGINT, SIGTERM, SIGHUP].contains(s))
+            {
+                return Ok(128 + signal);
+            }
+            // without the terminal, there's no way to quit otherwise.
+            if ready.hangup {
+                return Ok(128 + SIGHUP);
+            }
+            if ready.signals.contains(&SIGTSTP) {
+                suspend(&modes, &mut surface)?;
+            }
+            // might be continued after being stopped by something else;
+            // screen content is unknown then.
+            if ready.signals.contains(&SIGTSTP) || ready.signals.contains(&SIGCONT) {
+                surface.clear()?;
+                event_loop.on_resize(event_loop.width(&surface)?, surface.lines()?);
+                surface.render(&event_loop.state)?;
+                last_render = Some(Instant::now());
+                dirty = false;
+            }
+
+            if ready.signals.contains(&SIGWINCH)
+                && event_loop.on_resize(event_loop.width(&surface)?, surface.lines()?)
+            {
+                // resize is shown right away, regardless of the frame rate.
+                surface.render(&event_loop.state)?;
+                last_render = Some(Instant::now());
+                dirty = false;
+            }
+
+            let mut messages = vec![];
+            if ready.input {
+                reactor.read_input()?.into_iter().for_each(|e| match e {
+                    Event::Key(k) => messages.push(Message::KeyPress(k)),
+                    Event::Mouse(MouseEvent::Press(button, a, _)) => {
+                        messages.push(Message::MousePress((button, a)))
                     }
+                    _ => {}
+                });
+            }
+            if ready.wakeup {
+                reactor.clear_wakeup();
+                let n = messages.len();
+                messages.extend(event_loop.receiver.try_iter().take(QUEUE_SIZE));
+                received += messages.len() - n;
+                // there might be more; don't let them delay the frame.
+                if messages.len() - n >= QUEUE_SIZE {
+                    reactor.waker().wake();
+                } else if let Some(slice) = ingest.take_merged(received) {
+                    messages.push(Message::DataSlice(slice));
                 }
-                // key events; 'exit' handled right here, everything else - in dedicated handler.
-                Message::KeyPress(input) => {
-                    // TODO: handle signals (including ctrl+c) properly
-                    if input == Key::Char('q') || input == Key::Esc || input == Key::Ctrl('c') {
-                        break;
-                    }
-                    if event_loop.on_key_press(input, surface.width()?, surface.height()?) {
-                        surface.render(&event_loop.state)?;
+            }
+            for message in messages {
+                match event_loop.on_message(message, &surface)? {
+                    Next::Quit => return Ok(0),
+                    Next::Suspend => {
+                        suspend(&modes, &mut surface)?;
+                        event_loop.on_resize(event_loop.width(&surface)?, surface.lines()?);
+                        dirty = true;
+                        last_render = None;
                     }
+                    Next::Render => dirty = true,
+                    Next::Wait => {}
                 }
             }
-        }
 
-        Ok(())
+            if dirty && last_render.is_none_or(|t| t.elapsed() >= frame) {
+                event_loop.state.on_ingest(
+                    ingest.dropped(),
+                    ingest.merged(),
+                    ingest.take_lagging(),
+                );
+                surface.render(&event_loop.state)?;
+                last_render = Some(Instant::now());
+                dirty = false;
+            }
+        }
     }
 
-    // add new 'event producer'
-    fn add<F>(&self, producer: F) -> &EventLoop
-    where
-        F: Fn(mpsc::Sender<Message>) -> Result<(), Error>,
-        F: std::marker::Send,
-        F: 'static,
-    {
-        let sender = self.sender.clone();
-        thread::spawn(move || match producer(sender.clone()) {
-            Ok(_) => (),
-            Err(e) => eprintln!("error: {}", e),
-        });
-        self
+    fn on_message(&mut self, message: Message, surface: &impl Surface) -> Result<Next, Error> {
+        let changed = match message {
+            // Append new column, with potentially new column names.
+            // series might be reordered as a result of this operation.
+            Message::ExtendDataSet(d) => {
+                self.state.extend_dataset(d, self.width(surface)?);
+                true
+            }
+            // Append new slice to the existing set of columns.
+            Message::DataSlice(s) => {
+                self.state.append_slice(s, self.width(surface)?);
+                true
+            }
+            // Handle fetching error.
+            Message::FetchError(e) => {
+                // error will be cleared on next successful data fetch
+                self.state.on_error(format!("{}", e));
+                // we need to render to show 'error' to user.
+                true
+            }
+            // Rejected lines and values.
+            Message::Diagnostics(report) => {
+                self.state.diagnostics.on_report(report);
+                true
+            }
+            // mouse event; includes both press/scroll.
+            Message::MousePress((b, x)) => {
+                // x is 1-based; charts start after the gutter.
+                let x = x as i64 - 1 - gutter_width(self.state.is_compact()) as i64;
+                self.on_mouse_press(b, x, self.width(surface)?, surface.lines()?)
+            }
+            // key events; 'exit' handled right here, everything else - in dedicated handler.
+            Message::KeyPress(input) => {
+                // in raw mode, ctrl+c and ctrl+z come as keys, not signals.
+                if input == Key::Char('q') || input == Key::Esc || input == Key::Ctrl('c') {
+                    return Ok(Next::Quit);
+                }
+                if input == Key::Ctrl('z') {
+                    return Ok(Next::Suspend);
+                }
+                // layout change affects both view sizes.
+                if input == Key::Char('v') {
+                    self.state.toggle_compact();
+                    self.on_resize(self.width(surface)?, surface.lines()?);
+                    return Ok(Next::Render);
+                }
+                self.on_key_press(input, self.width(surface)?, surface.lines()?)
+            }
+        };
+        Ok(if changed { Next::Render } else { Next::Wait })
     }
 }
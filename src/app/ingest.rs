@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::app::event_loop::Message;
+use crate::app::reactor::Waker;
+use crate::data::series::{SeriesSet, Slice};
+use crate::data::unit::Unit;
+
+// capacity of the main loop queue, in messages.
+pub const QUEUE_SIZE: usize = 4096;
+
+/// What to do with new data when the main loop queue is full.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    // wait until there's space; input producer is slowed down as well.
+    Block,
+    // drop new columns; new headers and errors are never dropped.
+    Drop,
+    // merge new columns into one until there's space; new headers
+    // and errors wait for the space.
+    Aggregate,
+}
+
+impl Overflow {
+    pub fn new(conf: &str) -> Result<Overflow, IngestError> {
+        match conf {
+            "block" => Ok(Overflow::Block),
+            "drop" => Ok(Overflow::Drop),
+            "aggregate" => Ok(Overflow::Aggregate),
+            _ => Err(IngestError::BadOverflow(conf.to_owned())),
+        }
+    }
+}
+
+type Units = Option<Vec<Option<Unit>>>;
+
+// units given later win; positions without a unit keep the earlier one.
+fn merge_units(into: &mut Units, units: Units) {
+    if let Some(units) = units {
+        let into = into.get_or_insert_with(Vec::new);
+        if into.len() < units.len() {
+            into.resize(units.len(), None);
+        }
+        into.iter_mut()
+            .zip(units)
+            .filter(|(_, u)| u.is_some())
+            .for_each(|(i, u)| *i = u);
+    }
+}
+
+// Columns merged into one: x of the latest column, and the mean of
+// each series.
+struct Merged {
+    slice: Slice,
+    // number of values in the mean of each series.
+    counts: Vec<usize>,
+}
+
+impl Merged {
+    fn new(slice: Slice) -> Merged {
+        let counts = slice.y.iter().map(|v| v.is_finite() as usize).collect();
+        Merged { slice, counts }
+    }
+
+    fn merge(&mut self, slice: Slice) {
+        let (y, counts) = (&mut self.slice.y, &mut self.counts);
+        if y.len() < slice.y.len() {
+            y.resize(slice.y.len(), f64::NAN);
+            counts.resize(slice.y.len(), 0);
+        }
+        slice
+            .y
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.is_finite())
+            .for_each(|(i, v)| {
+                counts[i] += 1;
+                y[i] = match counts[i] {
+                    1 => *v,
+                    n => y[i] + (v - y[i]) / n as f64,
+                };
+            });
+        self.slice.x = slice.x;
+        self.slice.t = slice.t;
+        merge_units(&mut self.slice.units, slice.units);
+    }
+}
+
+// Data held back by the overflow policy.
+#[derive(Default)]
+struct Held {
+    // titles of the latest set; slices follow them.
+    titles: Vec<String>,
+    // units of the dropped columns, not delivered yet.
+    units: Units,
+    merged: Option<Merged>,
+}
+
+/// IngestStatus is shared between the producers and the main loop.
+#[derive(Default)]
+pub struct IngestStatus {
+    dropped: AtomicUsize,
+    merged: AtomicUsize,
+    // queue was full since the main loop last checked.
+    lagging: AtomicBool,
+    // messages put into the queue so far.
+    sent: AtomicUsize,
+    held: Mutex<Held>,
+}
+
+impl IngestStatus {
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // number of columns merged into others.
+    pub fn merged(&self) -> usize {
+        self.merged.load(Ordering::Relaxed)
+    }
+
+    // returns whether the queue was full since previous call.
+    pub fn take_lagging(&self) -> bool {
+        self.lagging.swap(false, Ordering::Relaxed)
+    }
+
+    // Takes the merged column once main loop has received everything
+    // queued before it, so it doesn't wait for the next column to be
+    // sent. 'received' is the number of messages taken from the queue.
+    pub fn take_merged(&self, received: usize) -> Option<Slice> {
+        // producer holds the lock while it's waiting for space.
+        let mut held = self.held.try_lock().ok()?;
+        if self.sent.load(Ordering::Relaxed) != received {
+            return None;
+        }
+        held.merged.take().map(|m| m.slice)
+    }
+}
+
+/// IngestQueue is the sending side of the bounded main loop queue.
+/// Main loop is woken up after every message sent.
+#[derive(Clone)]
+pub struct IngestQueue {
+    sender: mpsc::SyncSender<Message>,
+    overflow: Overflow,
+    status: Arc<IngestStatus>,
+    waker: Waker,
+}
+
+impl IngestQueue {
+    pub fn new(sender: mpsc::SyncSender<Message>, overflow: Overflow, waker: Waker) -> IngestQueue {
+        IngestQueue {
+            sender,
+            overflow,
+            status: Arc::new(IngestStatus::default()),
+            waker,
+        }
+    }
+
+    pub fn status(&self) -> Arc<IngestStatus> {
+        self.status.clone()
+    }
+
+    // sends the message, applying overflow policy to data columns.
+    pub fn send(&self, message: Message) -> Result<(), IngestError> {
+        let res = self.enqueue(message);
+        self.waker.wake();
+        res
+    }
+
+    // sends everything held back by the overflow policy.
+    pub fn flush(&self) -> Result<(), IngestError> {
+        let mut held = self.status.held.lock().unwrap();
+        if let Some(merged) = held.merged.take() {
+            self.wait(Message::DataSlice(merged.slice))?;
+        }
+        self.waker.wake();
+        Ok(())
+    }
+
+    fn enqueue(&self, message: Message) -> Result<(), IngestError> {
+        let mut held = self.status.held.lock().unwrap();
+        // merged column goes first, if there's space for it now.
+        if let Some(Merged { slice, counts }) = held.merged.take() {
+            if let Some(Message::DataSlice(slice)) = self.offer(Message::DataSlice(slice))? {
+                held.merged = Some(Merged { slice, counts });
+            }
+        }
+        match message {
+            Message::DataSlice(mut slice) => {
+                // units of the dropped columns come with the next one.
+                if let Some(units) = held.units.take() {
+                    let own = slice.units.replace(units);
+                    merge_units(&mut slice.units, own);
+                }
+                if let Some(merged) = held.merged.as_mut() {
+                    merged.merge(slice);
+                    self.status.merged.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                match (self.overflow, self.offer(Message::DataSlice(slice))?) {
+                    (_, None) => Ok(()),
+                    (Overflow::Drop, Some(Message::DataSlice(slice))) => {
+                        merge_units(&mut held.units, slice.units);
+                        self.status.dropped.fetch_add(1, Ordering::Relaxed);
+                        Ok(())
+                    }
+                    (Overflow::Aggregate, Some(Message::DataSlice(slice))) => {
+                        held.merged = Some(Merged::new(slice));
+                        Ok(())
+                    }
+                    (_, Some(message)) => self.wait(message),
+                }
+            }
+            mut message => {
+                if let Some(merged) = held.merged.take() {
+                    self.wait(Message::DataSlice(merged.slice))?;
+                }
+                if let Message::ExtendDataSet(set) = &mut message {
+                    let units = held.units.take();
+                    give_units(set, &held.titles, units);
+                    held.titles = set.y.iter().map(|s| s.title.clone()).collect();
+                }
+                match self.offer(message)? {
+                    None => Ok(()),
+                    Some(message) => self.wait(message),
+                }
+            }
+        }
+    }
+
+    // puts the message into the queue if there's space, otherwise
+    // returns it back.
+    fn offer(&self, message: Message) -> Result<Option<Message>, IngestError> {
+        match self.sender.try_send(message) {
+            Ok(()) => {
+                self.status.sent.fetch_add(1, Ordering::Relaxed);
+                Ok(None)
+            }
+            Err(mpsc::TrySendError::Full(message)) => {
+                self.status.lagging.store(true, Ordering::Relaxed);
+                Ok(Some(message))
+            }
+            Err(mpsc::TrySendError::Disconnected(_)) => Err(IngestError::Disconnected),
+        }
+    }
+
+    fn wait(&self, message: Message) -> Result<(), IngestError> {
+        self.sender
+            .send(message)
+            .map_err(|_| IngestError::Disconnected)?;
+        self.status.sent.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// Units of the dropped columns follow the titles of the previous set;
+// the new set delivers them to its series, unless it has own units.
+// Series missing from the new set don't get values anymore.
+fn give_units(set: &mut SeriesSet, titles: &[String], units: Units) {
+    let units: HashMap<&str, Unit> = match units {
+        Some(units) => titles
+            .iter()
+            .zip(units)
+            .filter_map(|(t, u)| u.map(|u| (t.as_str(), u)))
+            .collect(),
+        None => return,
+    };
+    set.y
+        .iter_mut()
+        .filter(|s| s.unit.is_none())
+        .for_each(|s| s.unit = units.get(s.title.as_str()).cloned());
+}
+
+#[derive(Debug)]
+pub enum IngestError {
+    BadOverflow(String),
+    // main loop is gone.
+    Disconnected,
+}
+
+impl std::fmt::Display for IngestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            IngestError::BadOverflow(ref s) => write!(f, "Unknown overflow policy: {}", s),
+            IngestError::Disconnected => write!(f, "main loop queue is closed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::reactor::wakeup_pipe;
+    use crate::data::series::{SeriesSet, Slice};
+    use crate::data::unit::Unit;
+
+    fn slice(x: &str, y: Vec<f64>) -> Message {
+        Message::DataSlice(Slice {
+            x: Some(x.to_owned()),
+            y,
+            ..Slice::default()
+        })
+    }
+
+    fn received(message: Message) -> Slice {
+        match message {
+            Message::DataSlice(slice) => slice,
+            _ => panic!("not a slice"),
+        }
+    }
+
+    #[test]
+    fn drops_columns_on_overflow() {
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let (_wakeup, waker) = wakeup_pipe().unwrap();
+        let queue = IngestQueue::new(sender, Overflow::Drop, waker);
+        let status = queue.status();
+        queue.send(Message::DataSlice(Slice::default())).unwrap();
+        assert!(!status.take_lagging());
+
+        queue.send(Message::DataSlice(Slice::default())).unwrap();
+        assert_eq!(status.dropped(), 1);
+        assert!(status.take_lagging());
+        assert!(!status.take_lagging());
+    }
+
+    #[test]
+    fn keeps_sets_on_overflow() {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let (_wakeup, waker) = wakeup_pipe().unwrap();
+        let queue = IngestQueue::new(sender, Overflow::Drop, waker);
+        let status = queue.status();
+        queue.send(Message::DataSlice(Slice::default())).unwrap();
+
+        // sets are not dropped, sender waits for the space instead.
+        let consumer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            receiver.iter().count()
+        });
+        queue
+            .send(Message::ExtendDataSet(SeriesSet::default()))
+            .unwrap();
+        drop(queue);
+        assert_eq!(consumer.join().unwrap(), 2);
+        assert_eq!(status.dropped(), 0);
+        assert!(status.take_lagging());
+    }
+
+    #[test]
+    fn merges_columns_on_overflow() {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let (_wakeup, waker) = wakeup_pipe().unwrap();
+        let queue = IngestQueue::new(sender, Overflow::Aggregate, waker);
+        let status = queue.status();
+        queue.send(slice("1", vec![1.0, 5.0])).unwrap();
+        queue.send(slice("2", vec![2.0, f64::NAN])).unwrap();
+        queue.send(slice("3", vec![4.0, f64::NAN])).unwrap();
+        assert_eq!(status.merged(), 1);
+        assert!(status.take_lagging());
+
+        // merged column is only taken after the queued ones.
+        assert!(status.take_merged(0).is_none());
+        assert_eq!(received(receiver.recv().unwrap()).y, vec![1.0, 5.0]);
+        let merged = status.take_merged(1).unwrap();
+        assert_eq!(merged.x, Some("3".to_owned()));
+        assert_eq!(merged.y[0], 3.0);
+        assert!(merged.y[1].is_nan());
+        assert!(status.take_merged(1).is_none());
+
+        // or it goes before the next set.
+        queue.send(slice("4", vec![1.0])).unwrap();
+        queue.send(slice("5", vec![3.0])).unwrap();
+        let consumer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            receiver
+                .iter()
+                .map(|m| match m {
+                    Message::DataSlice(slice) => slice.x.unwrap(),
+                    _ => "set".to_owned(),
+                })
+                .collect::<Vec<_>>()
+        });
+        queue
+            .send(Message::ExtendDataSet(SeriesSet::default()))
+            .unwrap();
+        drop(queue);
+        assert_eq!(consumer.join().unwrap(), vec!["4", "5", "set"]);
+    }
+
+    #[test]
+    fn keeps_units_of_dropped_columns() {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let (_wakeup, waker) = wakeup_pipe().unwrap();
+        let queue = IngestQueue::new(sender, Overflow::Drop, waker);
+        queue.send(slice("1", vec![1.0, 2.0])).unwrap();
+        queue
+            .send(Message::DataSlice(Slice {
+                y: vec![1.0, 2.0],
+                units: Some(vec![Some(Unit::Bytes), Some(Unit::Percent)]),
+                ..Slice::default()
+            }))
+            .unwrap();
+        assert_eq!(queue.status().dropped(), 1);
+
+        received(receiver.recv().unwrap());
+        queue
+            .send(Message::DataSlice(Slice {
+                y: vec![1.0, 2.0],
+                units: Some(vec![None, Some(Unit::Seconds)]),
+                ..Slice::default()
+            }))
+            .unwrap();
+        assert_eq!(
+            received(receiver.recv().unwrap()).units,
+            Some(vec![Some(Unit::Bytes), Some(Unit::Seconds)])
+        );
+    }
+
+    #[test]
+    fn overflow_config() {
+        assert_eq!(Overflow::new("block").unwrap(), Overflow::Block);
+        assert_eq!(Overflow::new("drop").unwrap(), Overflow::Drop);
+        assert_eq!(Overflow::new("aggregate").unwrap(), Overflow::Aggregate);
+        assert!(Overflow::new("merge").is_err());
+    }
+}
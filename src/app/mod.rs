@@ -1,3 +1,5 @@
 pub mod event_loop;
+pub mod ingest;
+pub mod reactor;
 pub mod settings;
 pub mod window;
@@ -0,0 +1,205 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use libc::c_int;
+
+use termion::event::Event;
+use termion::input::TermRead;
+
+/// Waker wakes the reactor up from other threads. It writes to the
+/// self-pipe the reactor is polling.
+#[derive(Clone)]
+pub struct Waker {
+    pipe: Arc<File>,
+}
+
+impl Waker {
+    pub fn wake(&self) {
+        // pipe is non-blocking; if it's full, reactor is going to
+        // wake up anyway.
+        let _ = (&*self.pipe).write(&[1]);
+    }
+}
+
+fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// returns reading end of the self-pipe and the waker for it.
+pub fn wakeup_pipe() -> io::Result<(File, Waker)> {
+    let mut fds: [RawFd; 2] = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (read, write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    set_nonblocking(fds[0])?;
+    set_nonblocking(fds[1])?;
+    Ok((
+        read,
+        Waker {
+            pipe: Arc::new(write),
+        },
+    ))
+}
+
+// What's ready after the wait.
+#[derive(Debug, Default)]
+pub struct Ready {
+    // keyboard or mouse input on the terminal.
+    pub input: bool,
+    // terminal hung up or failed; there will be no more input.
+    pub hangup: bool,
+    // some thread called the waker.
+    pub wakeup: bool,
+    // watched signals received since previous wait.
+    pub signals: Vec<c_int>,
+}
+
+/// Reactor waits for terminal input, wakeups from the data threads,
+/// signals and the frame deadline together, with a single poll(2) call.
+/// Timers of the data threads (--tick, --idle) wake it up through the
+/// waker, like any other data.
+pub struct Reactor {
+    tty: File,
+    wakeup: File,
+    waker: Waker,
+    // watched signals and flags set by their handlers.
+    signals: Vec<(c_int, Arc<AtomicBool>)>,
+}
+
+impl Reactor {
+    pub fn new() -> io::Result<Reactor> {
+        let (wakeup, waker) = wakeup_pipe()?;
+        Ok(Reactor {
+            // input might be piped to stdin, so keys are read from the tty.
+            tty: termion::get_tty()?,
+            wakeup,
+            waker,
+            signals: vec![],
+        })
+    }
+
+    // Signal handler sets the flag and writes to the self-pipe, so that
+    // a signal arriving right before the wait is not missed.
+    pub fn watch(&mut self, signal: c_int) -> io::Result<()> {
+        let flag = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(signal, flag.clone())?;
+        signal_hook::low_level::pipe::register(signal, self.waker.pipe.try_clone()?)?;
+        self.signals.push((signal, flag));
+        Ok(())
+    }
+
+    fn take_signals(&self) -> Vec<c_int> {
+        self.signals
+            .iter()
+            .filter(|(_, flag)| flag.swap(false, Ordering::Relaxed))
+            .map(|(signal, _)| *signal)
+            .collect()
+    }
+
+    pub fn waker(&self) -> Waker {
+        self.waker.clone()
+    }
+
+    // Waits until there's something to handle, or until the deadline.
+    pub fn wait(&mut self, deadline: Option<Instant>) -> io::Result<Ready> {
+        let mut fds = [
+            libc::pollfd {
+                fd: self.tty.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: self.wakeup.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        // rounded up, so that we don't spin before the deadline.
+        let timeout = deadline.map_or(-1, |d| {
+            let left = d.saturating_duration_since(Instant::now());
+            left.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32
+        });
+        let res = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+        if res < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(Ready {
+                    signals: self.take_signals(),
+                    ..Ready::default()
+                });
+            }
+            return Err(err);
+        }
+        Ok(Ready {
+            input: fds[0].revents & libc::POLLIN != 0,
+            hangup: fds[0].revents & (libc::POLLHUP | libc::POLLERR | libc::POLLNVAL) != 0,
+            wakeup: fds[1].revents != 0,
+            signals: self.take_signals(),
+        })
+    }
+
+    // reads the input which is available on the terminal.
+    pub fn read_input(&mut self) -> io::Result<Vec<Event>> {
+        let mut buf = [0u8; 1024];
+        let n = self.tty.read(&mut buf)?;
+        Ok(buf[..n].events().filter_map(Result::ok).collect())
+    }
+
+    // must be called before handling the messages the wakeup was for,
+    // otherwise wakeups for new messages could be lost.
+    pub fn clear_wakeup(&mut self) {
+        let mut buf = [0u8; 256];
+        while let Ok(n) = self.wakeup.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wakeup() {
+        let (mut read, waker) = wakeup_pipe().unwrap();
+        let mut buf = [0u8; 16];
+        assert!(read.read(&mut buf).is_err());
+
+        let w = waker.clone();
+        std::thread::spawn(move || w.wake()).join().unwrap();
+        waker.wake();
+        assert_eq!(read.read(&mut buf).unwrap(), 2);
+    }
+
+    #[test]
+    fn hangup() {
+        // pipe stands in for the terminal; closed writing end is a hangup.
+        let (tty, writer) = wakeup_pipe().unwrap();
+        let (wakeup, waker) = wakeup_pipe().unwrap();
+        let mut reactor = Reactor {
+            tty,
+            wakeup,
+            waker,
+            signals: vec![],
+        };
+        writer.wake();
+        let ready = reactor.wait(Some(Instant::now())).unwrap();
+        assert!(ready.input && !ready.hangup);
+        reactor.read_input().unwrap();
+
+        drop(writer);
+        let ready = reactor.wait(None).unwrap();
+        assert!(!ready.input && ready.hangup);
+    }
+}
@@ -1,11 +1,32 @@
+use crate::app::ingest::Overflow;
+use crate::data::resample::ResampleSettings;
+use crate::data::series::Retention;
+use crate::data::tick::TickSettings;
+use crate::data::time::{TimeParser, Timestamp};
+use crate::ui::glyphs::Glyphs;
+use crate::ui::palette::Palette;
+use crate::ui::theme::Theme;
+
+use regex::Regex;
+use std::collections::HashMap;
+
 #[derive(Clone, PartialEq)]
 pub enum Column {
-    #[allow(dead_code)]
     Index(usize),
     Title(String),
     None,
 }
 
+// Whether the first line of csv input is a header.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Header {
+    // header, unless the line parses entirely as numbers.
+    Auto,
+    Present,
+    // no header; titles might be provided by user.
+    Absent(Option<Vec<String>>),
+}
+
 #[derive(Debug, Clone)]
 pub enum SortingMode {
     ValuesDesc,
@@ -25,7 +46,30 @@ impl Column {
 pub struct Settings {
     pub input_file: Option<String>,
     pub x: Column,
+    pub header: Header,
+    pub only: Option<Regex>,
+    pub drop: Option<Regex>,
+    pub aliases: HashMap<String, String>,
+    pub time: Option<TimeParser>,
+    // how far to move the cursor on time-based navigation
+    pub jump: Timestamp,
+    pub resample: Option<ResampleSettings>,
+    pub tick: Option<TickSettings>,
+    pub keep: Option<Retention>,
     pub scales: Option<String>,
+    // chart rows per series, see RowsConfig.
+    pub rows: Option<String>,
+    // one line per series, with titles on the left.
+    pub compact: bool,
+    pub theme: Theme,
+    pub palette: Palette,
+    pub glyphs: Glyphs,
     pub paired: bool,
+    // pausing the view stops reading the input as well.
+    pub pause_input: bool,
+    pub idle: Option<Timestamp>,
     pub sort_mode: SortingMode,
+    // max number of screen updates per second.
+    pub fps: u32,
+    pub overflow: Overflow,
 }
@@ -12,6 +12,16 @@ pub struct Window {
     pub cursor: i64,
 }
 
+impl Window {
+    // first n data items were evicted; keeps the same data
+    // in the view and under the cursor, as long as it's still there.
+    pub fn on_evict(&mut self, n: i64) {
+        let position = self.offset + self.cursor - n;
+        self.offset = max(self.offset - n, 0);
+        self.cursor = max(position - self.offset, 0);
+    }
+}
+
 impl Default for Window {
     fn default() -> Window {
         Window {
@@ -33,22 +43,6 @@ pub struct WindowAdjust<'a> {
 }
 
 impl<'a> WindowAdjust<'a> {
-    pub fn halfpageup(&mut self) -> bool {
-        self.move_offset(-self.view_size / 2)
-    }
-
-    pub fn halfpagedown(&mut self) -> bool {
-        self.move_offset(self.view_size / 2)
-    }
-
-    pub fn pageup(&mut self) -> bool {
-        self.move_offset(-self.view_size)
-    }
-
-    pub fn pagedown(&mut self) -> bool {
-        self.move_offset(self.view_size)
-    }
-
     pub fn begin(&mut self) -> bool {
         self.set_offset(0)
     }
@@ -112,6 +106,19 @@ impl<'a> WindowAdjust<'a> {
         self.set_cursor(self.window.cursor + delta)
     }
 
+    // index of the data item under the cursor
+    pub fn cursor_position(&self) -> i64 {
+        self.window.offset + self.window.cursor
+    }
+
+    // screen size changed; keeps the same data item under the cursor.
+    pub fn on_resize(&mut self) -> bool {
+        let position = self.cursor_position();
+        self.set_offset(self.window.offset);
+        self.set_cursor(position - self.window.offset);
+        true
+    }
+
     // this runs on new data arrival;
     // if new data is significantly different
     // offset and cursor might not be valid anymore
@@ -129,7 +136,98 @@ impl<'a> WindowAdjust<'a> {
     }
 }
 
+// number of items with given heights which fit into the lines.
+pub fn fit<'a>(heights: impl Iterator<Item = &'a i64>, lines: i64) -> i64 {
+    let mut left = lines;
+    heights
+        .take_while(|h| {
+            left -= *h;
+            left >= 0
+        })
+        .count() as i64
+}
+
+// RowsAdjust handles vertical navigation. Unlike columns, series might
+// take different number of lines on the screen, so the view size
+// depends on where the view is. Window offset is the topmost series.
+pub struct RowsAdjust<'a> {
+    heights: Vec<i64>,
+    lines: i64,
+    window: &'a mut Window,
+}
+
+impl<'a> RowsAdjust<'a> {
+    pub fn new(heights: Vec<i64>, lines: i64, window: &'a mut Window) -> Self {
+        RowsAdjust {
+            heights,
+            lines,
+            window,
+        }
+    }
+
+    // number of series shown starting from the offset.
+    fn after(&self, offset: i64) -> i64 {
+        fit(self.heights.iter().skip(offset as usize), self.lines)
+    }
+
+    // number of series which fit right before the offset.
+    fn before(&self, offset: i64) -> i64 {
+        fit(self.heights.iter().take(offset as usize).rev(), self.lines)
+    }
+
+    // offset at which the last series is at the bottom.
+    fn last_offset(&self) -> i64 {
+        let n = self.heights.len() as i64;
+        n - self.before(n)
+    }
+
+    pub fn set_offset(&mut self, o: i64) -> bool {
+        let o = max(min(self.last_offset(), o), 0);
+        if o != self.window.offset {
+            self.window.offset = o;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn move_offset(&mut self, delta: i64) -> bool {
+        self.set_offset(self.window.offset + delta)
+    }
+
+    pub fn begin(&mut self) -> bool {
+        self.set_offset(0)
+    }
+
+    pub fn end(&mut self) -> bool {
+        self.set_offset(self.last_offset())
+    }
+
+    pub fn pagedown(&mut self) -> bool {
+        self.move_offset(self.after(self.window.offset))
+    }
+
+    pub fn pageup(&mut self) -> bool {
+        self.move_offset(-self.before(self.window.offset))
+    }
+
+    pub fn halfpagedown(&mut self) -> bool {
+        self.move_offset(self.after(self.window.offset) / 2)
+    }
+
+    pub fn halfpageup(&mut self) -> bool {
+        self.move_offset(-self.before(self.window.offset) / 2)
+    }
+
+    // keeps the view within the data.
+    pub fn clamp_offset(&mut self) -> bool {
+        self.set_offset(self.window.offset);
+        true
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -157,6 +255,34 @@ mod tests {
         assert_eq!(m.window.cursor, 0);
     }
 
+    #[test]
+    fn resize() {
+        let mut w = Window {
+            offset: 50,
+            cursor: 10,
+        };
+        // wider screen: view moves back, cursor stays on the same item.
+        WindowAdjust::new(100, 80, &mut w).on_resize();
+        assert_eq!((w.offset, w.cursor), (20, 40));
+        // narrower screen: cursor is kept within the screen.
+        WindowAdjust::new(100, 30, &mut w).on_resize();
+        assert_eq!((w.offset, w.cursor), (31, 29));
+    }
+
+    #[test]
+    fn evict() {
+        let mut w = Window {
+            offset: 10,
+            cursor: 5,
+        };
+        w.on_evict(3);
+        assert_eq!((w.offset, w.cursor), (7, 5));
+        w.on_evict(10);
+        assert_eq!((w.offset, w.cursor), (0, 2));
+        w.on_evict(10);
+        assert_eq!((w.offset, w.cursor), (0, 0));
+    }
+
     #[test]
     fn window_move() {
         let mut w = Window::default();
@@ -175,4 +301,28 @@ mod tests {
         assert_eq!(m.window.cursor, 9);
         assert_eq!(m.window.offset, 0);
     }
+
+    #[test]
+    fn rows() {
+        let mut w = Window {
+            offset: 8,
+            cursor: 1024,
+        };
+        // same heights work as fixed size view.
+        RowsAdjust::new(vec![2; 10], 10, &mut w).clamp_offset();
+        assert_eq!((w.offset, w.cursor), (5, 1024));
+
+        let heights = vec![2, 5, 2, 2, 3, 2];
+        let mut m = RowsAdjust::new(heights, 7, &mut w);
+        assert!(m.begin());
+        assert!(m.pagedown());
+        assert_eq!(m.window.offset, 2);
+        assert!(m.pagedown());
+        assert_eq!(m.window.offset, 3);
+        assert!(!m.end());
+        assert!(m.pageup());
+        assert_eq!(m.window.offset, 1);
+        assert!(m.halfpagedown());
+        assert_eq!(m.window.offset, 2);
+    }
 }
@@ -0,0 +1,103 @@
+// Reducer defines how several values which fall into the same
+// chart column are combined into one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reducer {
+    Last,
+    Mean,
+    Sum,
+    Max,
+    Count,
+}
+
+impl Reducer {
+    pub fn new(conf: &str) -> Result<Reducer, AggregateError> {
+        match conf {
+            "last" => Ok(Reducer::Last),
+            "mean" => Ok(Reducer::Mean),
+            "sum" => Ok(Reducer::Sum),
+            "max" => Ok(Reducer::Max),
+            "count" => Ok(Reducer::Count),
+            _ => Err(AggregateError::BadReducer(conf.to_owned())),
+        }
+    }
+}
+
+// Accumulator keeps enough information to apply any reducer.
+// NaN values are ignored; if there were no valid values, result is NaN
+// for every reducer except count.
+#[derive(Debug, Clone, Copy)]
+pub struct Accumulator {
+    sum: f64,
+    count: usize,
+    last: f64,
+    max: f64,
+}
+
+impl Default for Accumulator {
+    fn default() -> Accumulator {
+        Accumulator {
+            sum: 0.0,
+            count: 0,
+            last: f64::NAN,
+            max: f64::NAN,
+        }
+    }
+}
+
+impl Accumulator {
+    pub fn add(&mut self, v: f64) {
+        if v.is_nan() {
+            return;
+        }
+        self.sum += v;
+        self.count += 1;
+        self.last = v;
+        self.max = self.max.max(v);
+    }
+
+    pub fn value(&self, reducer: Reducer) -> f64 {
+        match reducer {
+            Reducer::Count => self.count as f64,
+            _ if self.count == 0 => f64::NAN,
+            Reducer::Last => self.last,
+            Reducer::Mean => self.sum / self.count as f64,
+            Reducer::Sum => self.sum,
+            Reducer::Max => self.max,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AggregateError {
+    BadReducer(String),
+}
+
+impl std::fmt::Display for AggregateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            AggregateError::BadReducer(ref s) => write!(f, "Unknown aggregation: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduce() {
+        let mut a = Accumulator::default();
+        assert!(a.value(Reducer::Mean).is_nan());
+        assert_eq!(a.value(Reducer::Count), 0.0);
+        a.add(1.0);
+        a.add(f64::NAN);
+        a.add(5.0);
+        a.add(3.0);
+        assert_eq!(a.value(Reducer::Last), 3.0);
+        assert_eq!(a.value(Reducer::Mean), 3.0);
+        assert_eq!(a.value(Reducer::Sum), 9.0);
+        assert_eq!(a.value(Reducer::Max), 5.0);
+        assert_eq!(a.value(Reducer::Count), 3.0);
+        assert!(Reducer::new("median").is_err());
+    }
+}
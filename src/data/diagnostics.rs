@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, VecDeque};
+
+// number of recent lines the rejection rate is computed over.
+const RATE_WINDOW: usize = 100;
+// rejection rate (in percents) which is considered high.
+const HIGH_RATE: usize = 10;
+// number of recent rejected lines kept for inspection.
+const KEEP_REJECTED: usize = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    // line was not valid UTF-8; it's still used, with invalid
+    // sequences replaced.
+    InvalidUtf8,
+    // title:value format, but no ':' in the line.
+    NotAPair,
+    // titles of the series which values failed to parse.
+    BadValues(Vec<String>),
+    // not a rejection, but a warning: new header doesn't have
+    // the series which had data under previous header.
+    DroppedSeries(Vec<String>),
+}
+
+impl std::fmt::Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Reason::InvalidUtf8 => write!(f, "invalid UTF-8"),
+            Reason::NotAPair => write!(f, "not a title:value pair"),
+            Reason::BadValues(ref titles) => write!(f, "bad value for {}", titles.join(", ")),
+            Reason::DroppedSeries(ref titles) => {
+                write!(f, "header dropped series {}", titles.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    // 1-based line number in the input.
+    pub line: usize,
+    pub text: String,
+    pub reason: Reason,
+}
+
+/// Report is sent by the reader to the main loop when there are new
+/// rejections, or when rejection rate went over or under the threshold.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub lines: usize,
+    pub rejected: Vec<Rejection>,
+    pub high_rate: bool,
+    // samples dropped by resampling since the previous report: later
+    // than --lateness, or too far ahead of all the others.
+    pub out_of_range: usize,
+}
+
+/// IngestStats is kept by the reader. It numbers input lines,
+/// collects rejections and tracks rejection rate over recent lines.
+#[derive(Debug, Default)]
+pub struct IngestStats {
+    lines: usize,
+    // whether each of the recent lines was rejected.
+    recent: VecDeque<bool>,
+    recent_rejected: usize,
+    pending: Vec<Rejection>,
+    high_rate: bool,
+}
+
+impl IngestStats {
+    // registers next line and returns its number.
+    pub fn line(&mut self) -> usize {
+        self.lines += 1;
+        self.recent.push_back(false);
+        if self.recent.len() > RATE_WINDOW && self.recent.pop_front() == Some(true) {
+            self.recent_rejected -= 1;
+        }
+        self.lines
+    }
+
+    pub fn reject(&mut self, line: usize, text: &str, reason: Reason) {
+        let first = self.lines + 1 - self.recent.len();
+        if let Some(r) = line.checked_sub(first).and_then(|i| self.recent.get_mut(i)) {
+            if !*r {
+                *r = true;
+                self.recent_rejected += 1;
+            }
+        }
+        self.pending.push(Rejection {
+            line,
+            text: text.to_owned(),
+            reason,
+        });
+    }
+
+    // warnings are reported, but don't affect rejection rate.
+    pub fn warn(&mut self, line: usize, text: &str, reason: Reason) {
+        self.pending.push(Rejection {
+            line,
+            text: text.to_owned(),
+            reason,
+        });
+    }
+
+    fn is_high_rate(&self) -> bool {
+        self.recent_rejected * 100 >= HIGH_RATE * self.recent.len().max(1)
+            && self.recent_rejected > 0
+    }
+
+    // returns the report if there's anything new to tell.
+    pub fn report(&mut self) -> Option<Report> {
+        let high_rate = self.is_high_rate();
+        if self.pending.is_empty() && high_rate == self.high_rate {
+            return None;
+        }
+        self.high_rate = high_rate;
+        Some(Report {
+            lines: self.lines,
+            rejected: std::mem::take(&mut self.pending),
+            high_rate,
+            out_of_range: 0,
+        })
+    }
+}
+
+/// Diagnostics is what main loop knows about the ingestion problems.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    pub lines: usize,
+    pub rejected: usize,
+    // number of values failed to parse, per series title.
+    pub failures: BTreeMap<String, usize>,
+    // last rejected lines, oldest first.
+    pub last: VecDeque<Rejection>,
+    pub high_rate: bool,
+    // samples dropped by resampling.
+    pub out_of_range: usize,
+    // latest warning which was not looked at yet.
+    pub warning: Option<String>,
+}
+
+impl Diagnostics {
+    pub fn on_report(&mut self, report: Report) {
+        self.lines = report.lines;
+        self.high_rate = report.high_rate;
+        self.out_of_range += report.out_of_range;
+        report.rejected.into_iter().for_each(|r| {
+            match &r.reason {
+                Reason::DroppedSeries(_) => self.warning = Some(format!("{}", r.reason)),
+                Reason::BadValues(titles) => {
+                    self.rejected += 1;
+                    titles
+                        .iter()
+                        .for_each(|t| *self.failures.entry(t.clone()).or_insert(0) += 1);
+                }
+                _ => self.rejected += 1,
+            }
+            self.last.push_back(r);
+            if self.last.len() > KEEP_REJECTED {
+                self.last.pop_front();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejection_rate() {
+        let mut stats = IngestStats::default();
+        (0..50).for_each(|_| {
+            stats.line();
+        });
+        assert!(stats.report().is_none());
+
+        let line = stats.line();
+        stats.reject(line, "1,x", Reason::BadValues(vec!["b".to_owned()]));
+        let report = stats.report().unwrap();
+        assert_eq!(report.lines, 51);
+        assert_eq!(report.rejected[0].line, 51);
+        assert!(!report.high_rate);
+
+        (0..10).for_each(|_| {
+            let line = stats.line();
+            stats.reject(line, "\u{fffd}", Reason::InvalidUtf8);
+        });
+        assert!(stats.report().unwrap().high_rate);
+
+        // rate goes down as rejected lines leave the window.
+        (0..100).for_each(|_| {
+            stats.line();
+        });
+        let report = stats.report().unwrap();
+        assert!(report.rejected.is_empty());
+        assert!(!report.high_rate);
+        assert!(stats.report().is_none());
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut d = Diagnostics::default();
+        let rejected = (1..=KEEP_REJECTED + 1)
+            .map(|line| Rejection {
+                line,
+                text: "x,y".to_owned(),
+                reason: Reason::BadValues(vec!["a".to_owned(), "b".to_owned()]),
+            })
+            .collect();
+        d.on_report(Report {
+            lines: 100,
+            rejected,
+            high_rate: true,
+            out_of_range: 0,
+        });
+        assert_eq!(d.rejected, KEEP_REJECTED + 1);
+        assert_eq!(d.failures.get("a"), Some(&(KEEP_REJECTED + 1)));
+        assert_eq!(d.last.len(), KEEP_REJECTED);
+        assert_eq!(d.last[0].line, 2);
+        assert_eq!(d.warning, None);
+
+        d.on_report(Report {
+            lines: 101,
+            rejected: vec![Rejection {
+                line: 101,
+                text: "a,c".to_owned(),
+                reason: Reason::DroppedSeries(vec!["b".to_owned()]),
+            }],
+            high_rate: true,
+            out_of_range: 2,
+        });
+        assert_eq!(d.rejected, KEEP_REJECTED + 1);
+        assert_eq!(d.warning, Some("header dropped series b".to_owned()));
+        assert_eq!(d.out_of_range, 2);
+    }
+}
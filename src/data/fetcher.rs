@@ -1,124 +1,368 @@
 use crate::app::event_loop::Message;
+use crate::app::ingest::IngestQueue;
 use crate::app::settings::Column;
+use crate::data::diagnostics::{IngestStats, Reason, Report};
 use crate::data::fetcher_loop::{FetcherError, FetcherEvent, FetcherSettings};
-use crate::data::schema::Schema;
+use crate::data::resample::Resampler;
+use crate::data::schema::{Schema, SchemaSettings};
 use crate::data::series::{SeriesSet, Slice};
+use crate::data::tick::{TickAggregator, TickSettings};
+use crate::data::time::{now, TimeFormat, TimeParser, Zone};
 
+use std::collections::{HashSet, VecDeque};
 use std::fs::File;
 use std::io::stdin;
 use std::io::BufRead;
 use std::io::BufReader;
-use std::io::Lines;
 use std::io::Read;
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::Duration;
 
-trait Reader {
+pub trait Reader {
     fn next(&mut self) -> Result<ReaderMessage, FetcherError>;
 }
 
 pub enum ReaderMessage {
     Extend(SeriesSet),
     Append(Slice),
-    EOF,
+    // problems with the input, see diagnostics.
+    Report(Report),
+    Eof,
 }
 
+// Line of the input; invalid UTF-8 sequences are replaced,
+// and the line is marked as lossy.
+struct Line {
+    text: String,
+    lossy: bool,
+}
+
+// Same as BufRead::lines, but doesn't fail on invalid UTF-8.
+struct LossyLines<R: Read> {
+    reader: BufReader<R>,
+}
+
+impl<R: Read> LossyLines<R> {
+    fn new(reader: R) -> Self {
+        LossyLines {
+            reader: BufReader::new(reader),
+        }
+    }
+}
+
+impl<R: Read> Iterator for LossyLines<R> {
+    type Item = std::io::Result<Line>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = vec![];
+        match self.reader.read_until(b'\n', &mut buf) {
+            Ok(0) => None,
+            Ok(_) => {
+                if buf.last() == Some(&b'\n') {
+                    buf.pop();
+                    if buf.last() == Some(&b'\r') {
+                        buf.pop();
+                    }
+                }
+                Some(Ok(match String::from_utf8(buf) {
+                    Ok(text) => Line { text, lossy: false },
+                    Err(e) => Line {
+                        text: String::from_utf8_lossy(e.as_bytes()).into_owned(),
+                        lossy: true,
+                    },
+                }))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+enum NextLine {
+    Line(Line),
+    // nothing arrived within idle timeout
+    Idle,
+    Failed(std::io::Error),
+    End,
+}
+
+// Lines read ahead by the timed reader thread.
+const LINES_AHEAD: usize = 16;
+
+// Lines of the input which can be waited on with a timeout.
+// With timeout, reading happens in a separate thread.
+enum PairLines<R: Read> {
+    Blocking(LossyLines<R>),
+    Timed(mpsc::Receiver<std::io::Result<Line>>, Duration),
+}
+
+impl<R: Read + Send + 'static> PairLines<R> {
+    fn new(reader: R, idle: Option<Duration>) -> Self {
+        let lines = LossyLines::new(reader);
+        match idle {
+            None => PairLines::Blocking(lines),
+            Some(idle) => {
+                // bounded, so that reading still waits for the consumer.
+                let (sender, receiver) = mpsc::sync_channel(LINES_AHEAD);
+                std::thread::spawn(move || {
+                    for line in lines {
+                        let failed = line.is_err();
+                        if sender.send(line).is_err() || failed {
+                            break;
+                        }
+                    }
+                });
+                PairLines::Timed(receiver, idle)
+            }
+        }
+    }
+
+    // if 'wait' is false, blocks until next line is available.
+    fn next(&mut self, wait: bool) -> NextLine {
+        let line = match self {
+            PairLines::Blocking(lines) => lines.next(),
+            PairLines::Timed(receiver, _) if !wait => receiver.recv().ok(),
+            PairLines::Timed(receiver, idle) => match receiver.recv_timeout(*idle) {
+                Ok(line) => Some(line),
+                Err(mpsc::RecvTimeoutError::Timeout) => return NextLine::Idle,
+                Err(mpsc::RecvTimeoutError::Disconnected) => None,
+            },
+        };
+        match line {
+            Some(Ok(l)) => NextLine::Line(l),
+            Some(Err(e)) => NextLine::Failed(e),
+            None => NextLine::End,
+        }
+    }
+}
+
+/// PairReader reads title:value pairs. Column is complete when
+/// - empty line is encountered;
+/// - title repeats within the current column;
+/// - no new lines arrived within idle timeout, if configured.
 pub struct PairReader<R: Read> {
-    lines: Lines<BufReader<R>>,
-    x: Column,
+    lines: PairLines<R>,
+    schema_settings: SchemaSettings,
+    titles: Vec<String>,
+    values: Vec<String>,
+    // line numbers of the pairs.
+    line_numbers: Vec<usize>,
+    seen: HashSet<String>,
+    stats: IngestStats,
+    out: VecDeque<ReaderMessage>,
 }
 
-impl<R: Read> PairReader<R> {
-    pub fn new(reader: R, x: Column) -> Self {
-        let br = BufReader::new(reader);
+// Reports go out before the message they were collected with.
+fn with_report(
+    stats: &mut IngestStats,
+    out: &mut VecDeque<ReaderMessage>,
+    message: ReaderMessage,
+) -> ReaderMessage {
+    match stats.report() {
+        Some(report) => {
+            out.push_back(message);
+            ReaderMessage::Report(report)
+        }
+        None => message,
+    }
+}
+
+impl<R: Read + Send + 'static> PairReader<R> {
+    pub fn new(reader: R, schema_settings: SchemaSettings, idle: Option<Duration>) -> Self {
         PairReader::<R> {
-            lines: br.lines(),
-            x,
+            lines: PairLines::new(reader, idle),
+            schema_settings,
+            titles: vec![],
+            values: vec![],
+            line_numbers: vec![],
+            seen: HashSet::new(),
+            stats: IngestStats::default(),
+            out: VecDeque::new(),
+        }
+    }
+
+    fn flush(&mut self) -> SeriesSet {
+        if self.titles.is_empty() {
+            return SeriesSet::default();
         }
+        let mut schema = Schema::from_title_range(&self.schema_settings, &self.titles);
+        let mut data = schema.empty_set();
+        data.append_slice(schema.slice_from_range(&self.values));
+        schema.take_failures().into_iter().for_each(|(i, title)| {
+            let text = format!("{}:{}", self.titles[i], self.values[i]);
+            self.stats
+                .reject(self.line_numbers[i], &text, Reason::BadValues(vec![title]));
+        });
+        self.titles.clear();
+        self.values.clear();
+        self.line_numbers.clear();
+        self.seen.clear();
+        data
+    }
+
+    fn push(&mut self, title: &str, value: &str, line: usize) {
+        self.seen.insert(title.to_owned());
+        self.titles.push(title.to_owned());
+        self.values.push(value.to_owned());
+        self.line_numbers.push(line);
+    }
+
+    fn extend(&mut self) -> ReaderMessage {
+        let data = self.flush();
+        with_report(&mut self.stats, &mut self.out, ReaderMessage::Extend(data))
     }
 }
 
-impl<R: Read> Reader for PairReader<R> {
+impl<R: Read + Send + 'static> Reader for PairReader<R> {
     fn next(&mut self) -> Result<ReaderMessage, FetcherError> {
-        let mut titles = vec![];
-        let mut values = vec![];
+        if let Some(message) = self.out.pop_front() {
+            return Ok(message);
+        }
         loop {
-            match self.lines.next() {
-                Some(Ok(l)) => {
-                    if l != "" {
-                        let mut parts = l.split(':').take(2);
-                        match (parts.next(), parts.next()) {
-                            (Some(title), Some(value)) => {
-                                titles.push(title.to_owned());
-                                values.push(value.to_owned());
+            // idle timeout only makes sense if there's something to flush.
+            match self.lines.next(!self.titles.is_empty()) {
+                NextLine::Line(Line { text: l, lossy }) => {
+                    let n = self.stats.line();
+                    if lossy {
+                        self.stats.reject(n, &l, Reason::InvalidUtf8);
+                    }
+                    if l.is_empty() {
+                        return Ok(self.extend());
+                    }
+                    let mut parts = l.split(':').take(2);
+                    match (parts.next(), parts.next()) {
+                        (Some(title), Some(value)) => {
+                            if self.seen.contains(title) {
+                                let message = self.extend();
+                                self.push(title, value, n);
+                                return Ok(message);
                             }
-                            _ => {}
-                        };
-                    } else {
-                        // TODO: cleanup
-                        // empty line, flush
-                        if titles.len() > 0 {
-                            let schema = Schema::from_title_range(self.x.clone(), &titles);
-                            let mut data = schema.empty_set();
-                            data.append_slice(schema.slice_from_range(&values));
-                            return Ok(ReaderMessage::Extend(data));
-                        } else {
-                            return Ok(ReaderMessage::Extend(SeriesSet::default()));
+                            self.push(title, value, n);
                         }
+                        _ => self.stats.reject(n, &l, Reason::NotAPair),
                     }
                 }
-                _ => {
-                    if titles.len() > 0 {
-                        let schema = Schema::from_title_range(self.x.clone(), &titles);
-                        let mut data = schema.empty_set();
-                        data.append_slice(schema.slice_from_range(&values));
-                        return Ok(ReaderMessage::Extend(data));
-                    } else {
-                        return Ok(ReaderMessage::EOF);
+                NextLine::Idle => return Ok(self.extend()),
+                NextLine::Failed(e) => return Err(e.into()),
+                NextLine::End => {
+                    if self.titles.is_empty() {
+                        let stats = &mut self.stats;
+                        return Ok(with_report(stats, &mut self.out, ReaderMessage::Eof));
                     }
+                    return Ok(self.extend());
                 }
             }
         }
     }
 }
 
+// formats the row, reporting values which failed to parse.
+fn slice_row(schema: &mut Schema, stats: &mut IngestStats, l: &str, n: usize) -> Slice {
+    let slice = schema.slice(l);
+    let failures = schema.take_failures();
+    if !failures.is_empty() {
+        let titles = failures.into_iter().map(|(_, t)| t).collect();
+        stats.reject(n, l, Reason::BadValues(titles));
+    }
+    slice
+}
+
 pub struct LineReader<R: Read> {
-    lines: Lines<BufReader<R>>,
+    lines: LossyLines<R>,
     schema: Option<Schema>,
-    x: Column,
+    // series which had data under previous header.
+    previous: Vec<String>,
+    schema_settings: SchemaSettings,
+    stats: IngestStats,
+    out: VecDeque<ReaderMessage>,
 }
 
 impl<R: Read> LineReader<R> {
-    pub fn new(reader: R, x: Column) -> Self {
-        let br = BufReader::new(reader);
+    pub fn new(reader: R, schema_settings: SchemaSettings) -> Self {
         LineReader::<R> {
-            lines: br.lines(),
+            lines: LossyLines::new(reader),
             schema: None,
-            x,
+            previous: vec![],
+            schema_settings,
+            stats: IngestStats::default(),
+            out: VecDeque::new(),
+        }
+    }
+
+    fn read_line(&mut self, l: &str, n: usize) -> Option<ReaderMessage> {
+        if l.is_empty() {
+            if let Some(schema) = self.schema.take() {
+                self.previous = schema.titles_with_data();
+            }
+            return None;
+        }
+        match self.schema.as_mut() {
+            None => match Schema::headerless(&self.schema_settings, l) {
+                // first line is data already.
+                Some(mut schema) => {
+                    self.check_dropped(&schema, l, n);
+                    let mut set = schema.empty_set();
+                    set.append_slice(slice_row(&mut schema, &mut self.stats, l, n));
+                    self.schema = Some(schema);
+                    Some(ReaderMessage::Extend(set))
+                }
+                None => {
+                    let schema = Schema::from_titles(&self.schema_settings, l);
+                    self.check_dropped(&schema, l, n);
+                    let set = schema.empty_set();
+                    self.schema = Some(schema);
+                    Some(ReaderMessage::Extend(set))
+                }
+            },
+            Some(schema) => {
+                if schema.units_row(l) {
+                    return None;
+                }
+                Some(ReaderMessage::Append(slice_row(
+                    schema,
+                    &mut self.stats,
+                    l,
+                    n,
+                )))
+            }
+        }
+    }
+}
+
+impl<R: Read> LineReader<R> {
+    // warns if new header lost series which had data.
+    fn check_dropped(&mut self, schema: &Schema, l: &str, n: usize) {
+        let titles = schema.titles();
+        let dropped: Vec<String> = std::mem::take(&mut self.previous)
+            .into_iter()
+            .filter(|t| !titles.contains(t))
+            .collect();
+        if !dropped.is_empty() {
+            self.stats.warn(n, l, Reason::DroppedSeries(dropped));
         }
     }
 }
 
 impl<R: Read> Reader for LineReader<R> {
     fn next(&mut self) -> Result<ReaderMessage, FetcherError> {
+        if let Some(message) = self.out.pop_front() {
+            return Ok(message);
+        }
         loop {
-            match self.lines.next() {
-                Some(Ok(l)) => {
-                    if l == "" {
-                        self.schema = None;
-                        continue;
+            let message = match self.lines.next() {
+                Some(Ok(Line { text, lossy })) => {
+                    let n = self.stats.line();
+                    if lossy {
+                        self.stats.reject(n, &text, Reason::InvalidUtf8);
                     }
-                    match self.schema.as_ref() {
-                        None => {
-                            self.schema = Some(Schema::from_titles(self.x.clone(), &l));
-                            return Ok(ReaderMessage::Extend(
-                                self.schema.as_ref().unwrap().empty_set(),
-                            ));
-                        }
-                        Some(schema) => return Ok(ReaderMessage::Append(schema.slice(&l))),
+                    match self.read_line(&text, n) {
+                        Some(message) => message,
+                        None => continue,
                     }
                 }
-                _ => return Ok(ReaderMessage::EOF),
-            }
+                Some(Err(e)) => return Err(e.into()),
+                None => ReaderMessage::Eof,
+            };
+            return Ok(with_report(&mut self.stats, &mut self.out, message));
         }
     }
 }
@@ -133,38 +377,148 @@ impl Fetcher {
     fn loop_with_reader(
         mut reader: impl Reader,
         from_main_loop: mpsc::Receiver<FetcherEvent>,
-        to_main_loop: &mpsc::Sender<Message>,
+        to_main_loop: &IngestQueue,
     ) -> Result<(), FetcherError> {
         loop {
             Fetcher::check_pause(&from_main_loop);
             match reader.next()? {
-                ReaderMessage::EOF => return Ok(()),
-                ReaderMessage::Append(slice) => {
-                    to_main_loop.send(Message::DataSlice(slice)).unwrap()
-                }
-                ReaderMessage::Extend(set) => {
-                    to_main_loop.send(Message::ExtendDataSet(set)).unwrap()
+                ReaderMessage::Eof => return Ok(()),
+                message => Self::send(to_main_loop, message),
+            }
+        }
+    }
+
+    fn send(to_main_loop: &IngestQueue, message: ReaderMessage) {
+        match message {
+            ReaderMessage::Append(slice) => to_main_loop.send(Message::DataSlice(slice)).unwrap(),
+            ReaderMessage::Extend(set) => to_main_loop.send(Message::ExtendDataSet(set)).unwrap(),
+            ReaderMessage::Report(report) => {
+                to_main_loop.send(Message::Diagnostics(report)).unwrap()
+            }
+            ReaderMessage::Eof => {}
+        }
+    }
+
+    // In tick mode every column corresponds to a wall-clock interval.
+    // Reader blocks on input, so it runs in its own thread and reports
+    // to the same queue ticks are delivered to.
+    fn loop_with_ticks(
+        settings: &FetcherSettings,
+        tick: &TickSettings,
+        mut reader: impl Reader + Send + 'static,
+        from_main_loop: mpsc::Receiver<FetcherEvent>,
+        to_fetcher: mpsc::Sender<FetcherEvent>,
+        to_main_loop: &IngestQueue,
+    ) -> Result<(), FetcherError> {
+        // Pause only comes with --pause-input; reading is stopped then.
+        let gate = Arc::new((Mutex::new(false), Condvar::new()));
+        let reader_gate = gate.clone();
+        std::thread::spawn(move || loop {
+            let (paused, resumed) = &*reader_gate;
+            drop(resumed.wait_while(paused.lock().unwrap(), |p| *p).unwrap());
+            let message = reader.next();
+            let done = matches!(message, Ok(ReaderMessage::Eof) | Err(_));
+            if to_fetcher
+                .send(FetcherEvent::Data(Box::new(message)))
+                .is_err()
+                || done
+            {
+                break;
+            }
+        });
+
+        let time = settings
+            .schema
+            .time
+            .clone()
+            .unwrap_or_else(|| TimeParser::new(TimeFormat::EpochNanos, Zone::Local));
+        let x_title = match &settings.schema.x {
+            Column::Title(title) => title.clone(),
+            _ => "time".to_owned(),
+        };
+        let mut aggregator = TickAggregator::new(tick, time, x_title);
+        let mut paused = false;
+
+        for event in from_main_loop.iter() {
+            match event {
+                // ticks are held while paused, there's nothing read to aggregate.
+                FetcherEvent::Tick if paused => {}
+                FetcherEvent::Tick => Self::send(to_main_loop, aggregator.tick(now())),
+                FetcherEvent::Data(message) => match *message {
+                    Ok(ReaderMessage::Eof) => {
+                        if aggregator.has_data() {
+                            Self::send(to_main_loop, aggregator.tick(now()));
+                        }
+                        return Ok(());
+                    }
+                    // diagnostics are not aggregated.
+                    Ok(ReaderMessage::Report(report)) => {
+                        Self::send(to_main_loop, ReaderMessage::Report(report))
+                    }
+                    Ok(message) => aggregator.on_message(message),
+                    Err(e) => return Err(e),
+                },
+                FetcherEvent::Pause => {
+                    let (lock, resumed) = &*gate;
+                    let mut p = lock.lock().unwrap();
+                    *p = !*p;
+                    paused = *p;
+                    resumed.notify_all();
                 }
             }
         }
+        Ok(())
+    }
+
+    // optionally puts tick aggregation or resampling layer on top of the format reader.
+    fn loop_with_layers(
+        settings: &FetcherSettings,
+        reader: impl Reader + Send + 'static,
+        from_main_loop: mpsc::Receiver<FetcherEvent>,
+        to_fetcher: mpsc::Sender<FetcherEvent>,
+        to_main_loop: &IngestQueue,
+    ) -> Result<(), FetcherError> {
+        if let Some(tick) = settings.tick.as_ref() {
+            return Self::loop_with_ticks(
+                settings,
+                tick,
+                reader,
+                from_main_loop,
+                to_fetcher,
+                to_main_loop,
+            );
+        }
+        match (settings.resample.as_ref(), settings.schema.time.as_ref()) {
+            (Some(resample), Some(time)) => Self::loop_with_reader(
+                Resampler::new(reader, resample.clone(), time.clone()),
+                from_main_loop,
+                to_main_loop,
+            ),
+            _ => Self::loop_with_reader(reader, from_main_loop, to_main_loop),
+        }
     }
 
     fn read_from(
         settings: &FetcherSettings,
-        reader: impl Read,
+        reader: impl Read + Send + 'static,
         from_main_loop: mpsc::Receiver<FetcherEvent>,
-        to_main_loop: &mpsc::Sender<Message>,
+        to_fetcher: mpsc::Sender<FetcherEvent>,
+        to_main_loop: &IngestQueue,
     ) -> Result<(), FetcherError> {
         if settings.paired {
-            Self::loop_with_reader(
-                PairReader::new(reader, settings.x.clone()),
+            Self::loop_with_layers(
+                settings,
+                PairReader::new(reader, settings.schema.clone(), settings.idle),
                 from_main_loop,
+                to_fetcher,
                 to_main_loop,
             )
         } else {
-            Self::loop_with_reader(
-                LineReader::new(reader, settings.x.clone()),
+            Self::loop_with_layers(
+                settings,
+                LineReader::new(reader, settings.schema.clone()),
                 from_main_loop,
+                to_fetcher,
                 to_main_loop,
             )
         }
@@ -184,31 +538,169 @@ impl Fetcher {
     fn read(
         settings: FetcherSettings,
         from_main_loop: mpsc::Receiver<FetcherEvent>,
-        to_main_loop: &mpsc::Sender<Message>,
+        to_fetcher: mpsc::Sender<FetcherEvent>,
+        to_main_loop: &IngestQueue,
     ) -> Result<(), FetcherError> {
-        if let Some(input_file) = settings.input_file.as_ref() {
-            Fetcher::read_from(
-                &settings,
-                File::open(&input_file)?,
-                from_main_loop,
-                &to_main_loop,
-            )
-        } else {
-            let stdin = stdin();
-            Fetcher::read_from(&settings, stdin.lock(), from_main_loop, &to_main_loop)
-        }
+        let input: Box<dyn Read + Send> = match settings.input_file.as_ref() {
+            Some(input_file) => Box::new(File::open(input_file)?),
+            None => Box::new(stdin()),
+        };
+        Fetcher::read_from(&settings, input, from_main_loop, to_fetcher, to_main_loop)
     }
 
     pub fn fetcher_loop(
         &self,
         settings: FetcherSettings,
         from_main_loop: mpsc::Receiver<FetcherEvent>,
-        to_main_loop: mpsc::Sender<Message>,
+        to_fetcher: mpsc::Sender<FetcherEvent>,
+        to_main_loop: IngestQueue,
     ) {
         std::thread::spawn(move || {
-            if let Err(e) = Fetcher::read(settings, from_main_loop, &to_main_loop) {
-                to_main_loop.send(Message::FetchError(e)).unwrap();
+            match Fetcher::read(settings, from_main_loop, to_fetcher, &to_main_loop) {
+                Err(e) => to_main_loop.send(Message::FetchError(e)).unwrap(),
+                // columns merged on overflow are not left behind.
+                Ok(()) => to_main_loop.flush().unwrap(),
             }
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::settings::Header;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use std::thread;
+
+    fn settings() -> SchemaSettings {
+        SchemaSettings {
+            x: Column::None,
+            header: Header::Auto,
+            time: None,
+            only: None,
+            drop: None,
+            aliases: HashMap::new(),
+        }
+    }
+
+    fn titles(message: ReaderMessage) -> Vec<String> {
+        match message {
+            ReaderMessage::Extend(set) => set.y.into_iter().map(|s| s.title).collect(),
+            _ => panic!("pair reader must produce sets"),
+        }
+    }
+
+    #[test]
+    fn line_reader_reports_rejections() {
+        let input = Cursor::new(b"a,b\n1,2\n1,x\n\xff3,4\n".to_vec());
+        let mut reader = LineReader::new(input, settings());
+        assert!(matches!(reader.next().unwrap(), ReaderMessage::Extend(_)));
+        assert!(matches!(reader.next().unwrap(), ReaderMessage::Append(_)));
+        let report = match reader.next().unwrap() {
+            ReaderMessage::Report(report) => report,
+            _ => panic!("rejected line must be reported"),
+        };
+        assert_eq!(report.rejected[0].line, 3);
+        assert_eq!(
+            report.rejected[0].reason,
+            Reason::BadValues(vec!["b".to_owned()])
+        );
+        assert!(report.high_rate);
+        assert!(matches!(reader.next().unwrap(), ReaderMessage::Append(_)));
+
+        // invalid UTF-8 doesn't stop the reader
+        let report = match reader.next().unwrap() {
+            ReaderMessage::Report(report) => report,
+            _ => panic!("rejected line must be reported"),
+        };
+        let reasons: Vec<Reason> = report.rejected.into_iter().map(|r| r.reason).collect();
+        assert_eq!(
+            reasons,
+            vec![Reason::InvalidUtf8, Reason::BadValues(vec!["a".to_owned()])]
+        );
+        match reader.next().unwrap() {
+            ReaderMessage::Append(slice) => assert_eq!(slice.y[1], 4.0),
+            _ => panic!("lossy line must still be read"),
+        }
+        assert!(matches!(reader.next().unwrap(), ReaderMessage::Eof));
+    }
+
+    #[test]
+    fn line_reader_detects_missing_header() {
+        let input = Cursor::new("1,2\n3,4\n\na,b\n5,6\n");
+        let mut reader = LineReader::new(input, settings());
+        match reader.next().unwrap() {
+            ReaderMessage::Extend(set) => {
+                assert_eq!(set.y[1].title, "c2");
+                assert_eq!(set.y[1].values, vec![2.0]);
+            }
+            _ => panic!("first line must produce a set"),
+        }
+        assert!(matches!(reader.next().unwrap(), ReaderMessage::Append(_)));
+        // generated titles are gone with the new header.
+        assert!(matches!(reader.next().unwrap(), ReaderMessage::Report(_)));
+        assert_eq!(titles(reader.next().unwrap()), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn line_reader_warns_on_dropped_series() {
+        let input = Cursor::new("a,b,c\n1,2,\n\na,c\n3,4\n");
+        let mut reader = LineReader::new(input, settings());
+        assert_eq!(titles(reader.next().unwrap()), vec!["a", "b", "c"]);
+        assert!(matches!(reader.next().unwrap(), ReaderMessage::Append(_)));
+        match reader.next().unwrap() {
+            ReaderMessage::Report(report) => assert_eq!(
+                report.rejected[0].reason,
+                Reason::DroppedSeries(vec!["b".to_owned()])
+            ),
+            _ => panic!("dropped series must be reported"),
+        }
+        assert_eq!(titles(reader.next().unwrap()), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn pair_reader_splits_on_repeated_title() {
+        let input = Cursor::new("a:1\nb:2\na:3\nb:4\n\nc:5\n");
+        let mut reader = PairReader::new(input, settings(), None);
+        assert_eq!(titles(reader.next().unwrap()), vec!["a", "b"]);
+        assert_eq!(titles(reader.next().unwrap()), vec!["a", "b"]);
+        assert_eq!(titles(reader.next().unwrap()), vec!["c"]);
+        assert!(matches!(reader.next().unwrap(), ReaderMessage::Eof));
+    }
+
+    // returns data in chunks, sleeping before every chunk but first.
+    struct SlowReader {
+        chunks: Vec<&'static str>,
+        delay: Duration,
+        first: bool,
+    }
+
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.chunks.is_empty() {
+                return Ok(0);
+            }
+            if !self.first {
+                thread::sleep(self.delay);
+            }
+            self.first = false;
+            let chunk = self.chunks.remove(0).as_bytes();
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[test]
+    fn pair_reader_flushes_on_idle() {
+        let input = SlowReader {
+            chunks: vec!["a:1\nb:2\n", "c:3\n"],
+            delay: Duration::from_millis(300),
+            first: true,
+        };
+        let mut reader = PairReader::new(input, settings(), Some(Duration::from_millis(50)));
+        assert_eq!(titles(reader.next().unwrap()), vec!["a", "b"]);
+        assert_eq!(titles(reader.next().unwrap()), vec!["c"]);
+        assert!(matches!(reader.next().unwrap(), ReaderMessage::Eof));
+    }
+}
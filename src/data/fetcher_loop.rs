@@ -1,12 +1,22 @@
-use crate::app::event_loop::Message;
-use crate::app::settings::{Column, Settings};
-use crate::data::fetcher::Fetcher;
+use crate::app::ingest::IngestQueue;
+use crate::app::settings::Settings;
+use crate::data::fetcher::{Fetcher, ReaderMessage};
+use crate::data::resample::ResampleSettings;
+use crate::data::schema::SchemaSettings;
+use crate::data::tick::TickSettings;
+use crate::data::time::Timestamp;
 
 use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub enum FetcherEvent {
     Tick,
     Pause,
+    // in tick mode, reading happens in a separate thread, which
+    // reports to the fetcher through the same queue; boxed, as sets
+    // are much larger than the other events.
+    Data(Box<Result<ReaderMessage, FetcherError>>),
 }
 
 /// FetcherLoop is responsbile for setting up and maintaining
@@ -14,39 +24,91 @@ pub enum FetcherEvent {
 /// It spawns a new thread where data reading will happen.
 pub struct FetcherLoop {
     sender_to_fetcher: mpsc::Sender<FetcherEvent>,
+    tick_interval: Option<Timestamp>,
+    pause_input: bool,
 }
 
 pub struct FetcherSettings {
     pub input_file: Option<String>,
-    pub x: Column,
+    pub schema: SchemaSettings,
+    pub resample: Option<ResampleSettings>,
+    pub tick: Option<TickSettings>,
     pub paired: bool,
+    // pair format: flush the column if no lines arrived within this time.
+    pub idle: Option<Duration>,
 }
 
 impl FetcherLoop {
     pub fn new(
-        to_main_loop: mpsc::Sender<Message>, // where to send fetched data
+        to_main_loop: IngestQueue, // where to send fetched data
         settings: &Settings,
     ) -> FetcherLoop {
         let (to_fetcher, from_main_loop) = mpsc::channel();
         let fetcher = Fetcher::new();
         let fetcher_settings = FetcherSettings {
             input_file: settings.input_file.clone(),
-            x: settings.x.clone(),
+            schema: SchemaSettings {
+                x: settings.x.clone(),
+                header: settings.header.clone(),
+                time: settings.time.clone(),
+                only: settings.only.clone(),
+                drop: settings.drop.clone(),
+                aliases: settings.aliases.clone(),
+            },
+            resample: settings.resample.clone(),
+            tick: settings.tick.clone(),
             paired: settings.paired,
+            idle: settings.idle.map(|idle| Duration::from_nanos(idle as u64)),
         };
-        fetcher.fetcher_loop(fetcher_settings, from_main_loop, to_main_loop.clone());
+        fetcher.fetcher_loop(
+            fetcher_settings,
+            from_main_loop,
+            to_fetcher.clone(),
+            to_main_loop.clone(),
+        );
         FetcherLoop {
             sender_to_fetcher: to_fetcher,
+            tick_interval: settings.tick.as_ref().map(|t| t.interval),
+            pause_input: settings.pause_input,
         }
     }
+
     pub fn fetch(&mut self) {
-        if let Err(_) = self.sender_to_fetcher.send(FetcherEvent::Tick) {
-            // TODO: fetching done. Update status to done
+        match self.tick_interval {
+            Some(interval) => self.start_ticks(interval),
+            None => {
+                if self.sender_to_fetcher.send(FetcherEvent::Tick).is_err() {
+                    // TODO: fetching done. Update status to done
+                }
+            }
         }
     }
 
+    // sends a tick to the fetcher every interval. Deadlines are computed
+    // from the start time, so ticks do not drift.
+    fn start_ticks(&self, interval: Timestamp) {
+        let sender = self.sender_to_fetcher.clone();
+        let interval = Duration::from_nanos(interval as u64);
+        thread::spawn(move || {
+            let start = Instant::now();
+            let mut deadline = start;
+            loop {
+                deadline += interval;
+                thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                if sender.send(FetcherEvent::Tick).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    // Pausing is only passed to the fetcher if it's configured to stop
+    // reading; otherwise data keeps flowing, and only the view is paused.
     pub fn pause(&mut self) {
-        if let Err(_) = self.sender_to_fetcher.send(FetcherEvent::Pause) {
+        if !self.pause_input {
+            return;
+        }
+        if self.sender_to_fetcher.send(FetcherEvent::Pause).is_err() {
             // TODO: fetching done. Update status to done
         }
     }
@@ -55,7 +117,7 @@ impl FetcherLoop {
 #[derive(Debug)]
 pub enum FetcherError {
     IO(std::io::Error),
-    CSV(csv::Error),
+    Csv(csv::Error),
 }
 
 impl From<std::io::Error> for FetcherError {
@@ -66,7 +128,7 @@ impl From<std::io::Error> for FetcherError {
 
 impl From<csv::Error> for FetcherError {
     fn from(err: csv::Error) -> FetcherError {
-        FetcherError::CSV(err)
+        FetcherError::Csv(err)
     }
 }
 
@@ -74,7 +136,7 @@ impl std::fmt::Display for FetcherError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
             FetcherError::IO(ref err) => write!(f, "IO error: {}", err),
-            FetcherError::CSV(ref err) => write!(f, "CSV parse error: {}", err),
+            FetcherError::Csv(ref err) => write!(f, "CSV parse error: {}", err),
         }
     }
 }
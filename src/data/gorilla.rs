@@ -0,0 +1,207 @@
+// Gorilla-style compression of f64 sequences: every value is XOR-ed
+// with the previous one, and only the meaningful bits of the result
+// are stored. Repeated values take 1 bit, slowly changing ones take
+// a few bits more than the number of changed bits.
+//
+// Encoding of a value after the first one:
+//   '0'                             -- same as previous;
+//   '10' + meaningful bits          -- fits into previous leading/trailing zeros window;
+//   '11' + 5 bits of leading zeros
+//        + 6 bits of (length - 1)
+//        + meaningful bits          -- new window.
+
+#[derive(Debug, Clone, Default)]
+struct BitWriter {
+    words: Vec<u64>,
+    // number of bits written
+    len: usize,
+}
+
+impl BitWriter {
+    // writes lowest n bits of v, most significant first.
+    fn write(&mut self, v: u64, n: u32) {
+        if n == 0 {
+            return;
+        }
+        let v = if n == 64 { v } else { v & ((1u64 << n) - 1) };
+        let used = (self.len % 64) as u32;
+        if used == 0 {
+            self.words.push(0);
+        }
+        let free = 64 - used;
+        let last = self.words.len() - 1;
+        if n <= free {
+            self.words[last] |= v << (free - n);
+        } else {
+            self.words[last] |= v >> (n - free);
+            self.words.push(v << (64 - (n - free)));
+        }
+        self.len += n as usize;
+    }
+}
+
+struct BitReader<'a> {
+    words: &'a [u64],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, n: u32) -> u64 {
+        if n == 0 {
+            return 0;
+        }
+        let word = self.pos / 64;
+        let used = (self.pos % 64) as u32;
+        let avail = 64 - used;
+        let res = if n <= avail {
+            (self.words[word] << used) >> (64 - n)
+        } else {
+            let hi = (self.words[word] << used) >> used;
+            let lo = self.words[word + 1] >> (64 - (n - avail));
+            (hi << (n - avail)) | lo
+        };
+        self.pos += n as usize;
+        res
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Encoder {
+    out: BitWriter,
+    prev: u64,
+    // leading and trailing zeros of the last stored XOR.
+    window: Option<(u32, u32)>,
+    count: usize,
+}
+
+impl Encoder {
+    pub fn push(&mut self, v: f64) {
+        let bits = v.to_bits();
+        if self.count == 0 {
+            self.out.write(bits, 64);
+        } else {
+            let xor = bits ^ self.prev;
+            if xor == 0 {
+                self.out.write(0, 1);
+            } else {
+                let leading = xor.leading_zeros().min(31);
+                let trailing = xor.trailing_zeros();
+                match self.window {
+                    Some((l, t)) if leading >= l && trailing >= t => {
+                        self.out.write(0b10, 2);
+                        self.out.write(xor >> t, 64 - l - t);
+                    }
+                    _ => {
+                        let len = 64 - leading - trailing;
+                        self.out.write(0b11, 2);
+                        self.out.write(leading as u64, 5);
+                        self.out.write((len - 1) as u64, 6);
+                        self.out.write(xor >> trailing, len);
+                        self.window = Some((leading, trailing));
+                    }
+                }
+            }
+        }
+        self.prev = bits;
+        self.count += 1;
+    }
+
+    pub fn finish(self) -> Vec<u64> {
+        self.out.words
+    }
+}
+
+pub struct Decoder<'a> {
+    input: BitReader<'a>,
+    prev: u64,
+    window: (u32, u32),
+    remaining: usize,
+    first: bool,
+}
+
+impl<'a> Decoder<'a> {
+    // decodes n values from the words produced by the encoder.
+    pub fn new(words: &'a [u64], n: usize) -> Self {
+        Decoder {
+            input: BitReader { words, pos: 0 },
+            prev: 0,
+            window: (0, 0),
+            remaining: n,
+            first: true,
+        }
+    }
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<f64> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        if self.first {
+            self.first = false;
+            self.prev = self.input.read(64);
+        } else if self.input.read(1) == 1 {
+            if self.input.read(1) == 1 {
+                let leading = self.input.read(5) as u32;
+                let len = self.input.read(6) as u32 + 1;
+                self.window = (leading, 64 - leading - len);
+            }
+            let (l, t) = self.window;
+            self.prev ^= self.input.read(64 - l - t) << t;
+        }
+        Some(f64::from_bits(self.prev))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(values: &[f64]) -> Vec<u64> {
+        let mut e = Encoder::default();
+        values.iter().for_each(|v| e.push(*v));
+        let words = e.finish();
+        let decoded: Vec<f64> = Decoder::new(&words, values.len()).collect();
+        assert_eq!(decoded.len(), values.len());
+        values
+            .iter()
+            .zip(decoded.iter())
+            .for_each(|(a, b)| assert_eq!(a.to_bits(), b.to_bits()));
+        words
+    }
+
+    #[test]
+    fn encode_decode() {
+        roundtrip(&[]);
+        roundtrip(&[1.0]);
+        roundtrip(&[
+            1.0,
+            1.0,
+            1.5,
+            -0.0,
+            f64::NAN,
+            f64::NAN,
+            f64::INFINITY,
+            f64::MIN_POSITIVE,
+            f64::MAX,
+            3.25,
+            1e-300,
+        ]);
+        let v: Vec<f64> = (0..1000).map(|i| (i as f64 * 0.1).sin() * 100.0).collect();
+        roundtrip(&v);
+    }
+
+    #[test]
+    fn compresses_repeated_values() {
+        let words = roundtrip(&[42.0; 640]);
+        // 64 bits of the first value, then 1 bit per value.
+        assert_eq!(words.len(), 11);
+    }
+}
@@ -1,4 +1,4 @@
-fn base<'a>(v: &'a str) -> &'a str {
+fn base(v: &str) -> &str {
     &v[0..v.len() - 1]
 }
 
@@ -1,8 +1,17 @@
+pub mod aggregate;
+pub mod diagnostics;
 pub mod fetcher;
 pub mod fetcher_loop;
+pub mod gorilla;
 pub mod metric_parse;
+pub mod resample;
+pub mod rows_config;
 pub mod scale;
 pub mod scale_config;
 pub mod schema;
 pub mod series;
 pub mod state;
+pub mod tick;
+pub mod time;
+pub mod unit;
+pub mod values;
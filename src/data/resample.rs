@@ -0,0 +1,461 @@
+use crate::data::aggregate::{Accumulator, Reducer};
+use crate::data::diagnostics::Report;
+use crate::data::fetcher::{Reader, ReaderMessage};
+use crate::data::fetcher_loop::FetcherError;
+use crate::data::series::{Series, SeriesSet, Slice};
+use crate::data::time::{TimeParser, Timestamp};
+use crate::data::unit::Unit;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+// gaps longer than that are shown as a single empty column, to avoid
+// queueing a lot of columns on clock jumps. Samples which are further
+// than that ahead of all the others are dropped, unless the next
+// sample confirms the jump.
+const MAX_GAP_COLUMNS: i64 = 1_000;
+
+#[derive(Debug, Clone)]
+pub struct ResampleSettings {
+    pub step: Timestamp,
+    pub reducer: Reducer,
+    // how long to wait for late samples before column is emitted
+    pub lateness: Timestamp,
+}
+
+/// Resampler sits between the reader and the series set and snaps
+/// samples to a fixed time grid. Samples falling into the same step
+/// are aggregated, missing steps become NaN columns, and samples
+/// arriving out of order are accepted as long as their column
+/// was not emitted yet. Columns hold all the series seen so far, so
+/// that headers coming and going don't close the pending steps.
+pub struct Resampler<R: Reader> {
+    reader: R,
+    settings: ResampleSettings,
+    time: TimeParser,
+    // position of each series seen so far in the emitted columns.
+    index: HashMap<String, usize>,
+    // positions of the current input columns in the emitted ones.
+    columns: Vec<usize>,
+    // step index -> per-series aggregates
+    pending: BTreeMap<i64, Vec<Accumulator>>,
+    // index of the next step to emit
+    next: Option<i64>,
+    // latest timestamp seen so far
+    watermark: Option<Timestamp>,
+    // previous sample was too far ahead of the watermark.
+    jumped: bool,
+    // samples dropped since the last report.
+    out_of_range: usize,
+    // lines read and rejection rate from the last report of the reader.
+    last_report: (usize, bool),
+    // units changed since last emitted column
+    units: Option<Vec<Option<Unit>>>,
+    out: VecDeque<ReaderMessage>,
+}
+
+impl<R: Reader> Resampler<R> {
+    pub fn new(reader: R, settings: ResampleSettings, time: TimeParser) -> Self {
+        Resampler {
+            reader,
+            settings,
+            time,
+            index: HashMap::new(),
+            columns: vec![],
+            pending: BTreeMap::new(),
+            next: None,
+            watermark: None,
+            jumped: false,
+            out_of_range: 0,
+            last_report: (0, false),
+            units: None,
+            out: VecDeque::new(),
+        }
+    }
+
+    fn add(&mut self, t: Option<Timestamp>, y: impl Iterator<Item = f64>) {
+        // samples without valid timestamp can't be placed on the grid.
+        let t = match t {
+            Some(t) => t,
+            None => return,
+        };
+        let step = t.div_euclid(self.settings.step);
+        if self.next.is_some_and(|n| step < n) {
+            // too late, column was already emitted.
+            self.out_of_range += 1;
+            return;
+        }
+        let far = self
+            .watermark
+            .is_some_and(|w| step - w.div_euclid(self.settings.step) > MAX_GAP_COLUMNS);
+        let confirmed = std::mem::replace(&mut self.jumped, far);
+        if far && !confirmed {
+            self.out_of_range += 1;
+            return;
+        }
+        self.watermark = Some(self.watermark.map_or(t, |w| w.max(t)));
+        let n = self.index.len();
+        let pending = self
+            .pending
+            .entry(step)
+            .or_insert_with(|| vec![Accumulator::default(); n]);
+        self.columns
+            .iter()
+            .zip(y)
+            .for_each(|(&i, v)| pending[i].add(v));
+    }
+
+    fn slice(&mut self, step: i64, y: Vec<f64>) -> Slice {
+        let t = step.saturating_mul(self.settings.step);
+        Slice {
+            x: Some(self.time.format_full(t)),
+            t: Some(Some(t)),
+            y,
+            units: self.units.take(),
+        }
+    }
+
+    fn emit(&mut self, step: i64, values: Vec<Accumulator>) {
+        if let Some(next) = self.next {
+            let n = self.index.len();
+            let gaps = if step - next > MAX_GAP_COLUMNS {
+                step - 1..step
+            } else {
+                next..step
+            };
+            gaps.for_each(|gap| {
+                let empty = self.slice(gap, vec![f64::NAN; n]);
+                self.out.push_back(ReaderMessage::Append(empty));
+            });
+        }
+        let reducer = self.settings.reducer;
+        let slice = self.slice(step, values.iter().map(|a| a.value(reducer)).collect());
+        self.out.push_back(ReaderMessage::Append(slice));
+        self.next = Some(step + 1);
+    }
+
+    // emits all columns which can no longer receive samples.
+    fn emit_ready(&mut self) {
+        let watermark = match self.watermark {
+            Some(w) => w,
+            None => return,
+        };
+        while let Some(&step) = self.pending.keys().next() {
+            let closes_at = (step + 1)
+                .saturating_mul(self.settings.step)
+                .saturating_add(self.settings.lateness);
+            if closes_at > watermark {
+                break;
+            }
+            let values = self.pending.remove(&step).unwrap();
+            self.emit(step, values);
+        }
+    }
+
+    fn flush(&mut self) {
+        while let Some(&step) = self.pending.keys().next() {
+            let values = self.pending.remove(&step).unwrap();
+            self.emit(step, values);
+        }
+    }
+
+    // Series not seen before are announced with all the known ones, in
+    // the order of the emitted columns, and pending steps get empty
+    // values for them; series missing from the set are left as they are.
+    fn extend(&mut self, set: SeriesSet) {
+        let mut added = false;
+        self.columns = set
+            .y
+            .iter()
+            .map(|s| match self.index.get(&s.title) {
+                Some(&i) => i,
+                None => {
+                    let i = self.index.len();
+                    self.index.insert(s.title.clone(), i);
+                    added = true;
+                    i
+                }
+            })
+            .collect();
+        if added {
+            let n = self.index.len();
+            self.pending
+                .values_mut()
+                .for_each(|p| p.resize(n, Accumulator::default()));
+            let mut all = vec![Series::with_title(""); n];
+            self.index
+                .iter()
+                .for_each(|(title, &i)| all[i] = Series::with_title(title));
+            self.columns
+                .iter()
+                .zip(set.y.iter())
+                .for_each(|(&i, s)| all[i].unit = s.unit.clone());
+            self.out.push_back(ReaderMessage::Extend(SeriesSet::new(
+                set.x
+                    .as_ref()
+                    .map(|(title, _)| (title.clone(), VecDeque::new())),
+                Some(VecDeque::new()),
+                all,
+            )));
+        }
+        if set.y.iter().any(|s| s.unit.is_some()) {
+            self.set_units(set.y.iter().map(|s| s.unit.clone()));
+        }
+        set.columns()
+            .enumerate()
+            .for_each(|(i, c)| self.add(set.time_at(i), c.into_iter()));
+    }
+
+    // reports dropped samples, if there are any.
+    fn report(&mut self) {
+        if self.out_of_range > 0 {
+            let (lines, high_rate) = self.last_report;
+            self.out.push_back(ReaderMessage::Report(Report {
+                lines,
+                rejected: vec![],
+                high_rate,
+                out_of_range: std::mem::take(&mut self.out_of_range),
+            }));
+        }
+    }
+
+    // units of the current input columns, on top of the ones not emitted yet.
+    fn set_units(&mut self, units: impl Iterator<Item = Option<Unit>>) {
+        let mut res = self
+            .units
+            .take()
+            .unwrap_or_else(|| vec![None; self.index.len()]);
+        res.resize(self.index.len(), None);
+        self.columns
+            .iter()
+            .zip(units)
+            .filter(|(_, u)| u.is_some())
+            .for_each(|(&i, u)| res[i] = u);
+        self.units = Some(res);
+    }
+}
+
+impl<R: Reader> Reader for Resampler<R> {
+    fn next(&mut self) -> Result<ReaderMessage, FetcherError> {
+        loop {
+            if let Some(message) = self.out.pop_front() {
+                return Ok(message);
+            }
+            match self.reader.next()? {
+                ReaderMessage::Eof => {
+                    self.flush();
+                    self.out.push_back(ReaderMessage::Eof);
+                }
+                ReaderMessage::Append(slice) => {
+                    if let Some(units) = slice.units {
+                        self.set_units(units.into_iter());
+                    }
+                    self.add(slice.t.flatten(), slice.y.into_iter());
+                    self.emit_ready();
+                    self.report();
+                }
+                ReaderMessage::Extend(set) => {
+                    self.extend(set);
+                    self.emit_ready();
+                    self.report();
+                }
+                ReaderMessage::Report(mut report) => {
+                    self.last_report = (report.lines, report.high_rate);
+                    report.out_of_range += std::mem::take(&mut self.out_of_range);
+                    self.out.push_back(ReaderMessage::Report(report));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::settings::SortingMode;
+    use crate::data::time::{TimeFormat, Zone};
+
+    struct VecReader {
+        messages: VecDeque<ReaderMessage>,
+    }
+
+    impl Reader for VecReader {
+        fn next(&mut self) -> Result<ReaderMessage, FetcherError> {
+            Ok(self.messages.pop_front().unwrap_or(ReaderMessage::Eof))
+        }
+    }
+
+    fn header() -> ReaderMessage {
+        ReaderMessage::Extend(SeriesSet::new(
+            Some(("ts".to_owned(), VecDeque::new())),
+            Some(VecDeque::new()),
+            vec![Series::with_title("a")],
+        ))
+    }
+
+    fn row(t: Timestamp, v: f64) -> ReaderMessage {
+        ReaderMessage::Append(Slice {
+            x: Some(t.to_string()),
+            t: Some(Some(t)),
+            y: vec![v],
+            units: None,
+        })
+    }
+
+    fn resample(messages: Vec<ReaderMessage>, reducer: Reducer, lateness: Timestamp) -> SeriesSet {
+        run(messages, reducer, lateness).0
+    }
+
+    // resampled set and number of samples dropped.
+    fn run(
+        messages: Vec<ReaderMessage>,
+        reducer: Reducer,
+        lateness: Timestamp,
+    ) -> (SeriesSet, usize) {
+        let settings = ResampleSettings {
+            step: 10,
+            reducer,
+            lateness,
+        };
+        let time = TimeParser::new(TimeFormat::EpochNanos, Zone::Utc);
+        let reader = VecReader {
+            messages: messages.into_iter().collect(),
+        };
+        let mut resampler = Resampler::new(reader, settings, time);
+        let mut res = SeriesSet::default();
+        let mut dropped = 0;
+        loop {
+            // series are sorted after every set, as the main loop does.
+            match resampler.next().unwrap() {
+                ReaderMessage::Eof => return (res, dropped),
+                ReaderMessage::Append(slice) => res.append_slice(slice),
+                ReaderMessage::Extend(set) => {
+                    res.append_set(set);
+                    res.order_by(&SortingMode::ValuesDesc);
+                }
+                ReaderMessage::Report(r) => dropped += r.out_of_range,
+            }
+        }
+    }
+
+    #[test]
+    fn aggregates_and_fills_gaps() {
+        let res = resample(
+            vec![
+                header(),
+                row(1, 1.0),
+                row(5, 3.0),
+                row(12, 5.0),
+                row(41, 7.0),
+            ],
+            Reducer::Mean,
+            0,
+        );
+        assert_eq!(
+            res.t,
+            Some(vec![Some(0), Some(10), Some(20), Some(30), Some(40)].into())
+        );
+        let v = &res.y[0].values;
+        assert!(v.range(0..2).eq([2.0, 5.0]));
+        assert!(v.range(2..4).all(f64::is_nan));
+        assert_eq!(v.get(4), Some(7.0));
+    }
+
+    #[test]
+    fn reorders_within_lateness() {
+        let rows = vec![
+            header(),
+            row(1, 1.0),
+            row(12, 2.0),
+            row(3, 3.0),
+            row(25, 4.0),
+        ];
+        let res = resample(rows, Reducer::Sum, 10);
+        assert_eq!(res.y[0].values, vec![4.0, 2.0, 4.0]);
+
+        // without lateness, late sample is dropped.
+        let rows = vec![
+            header(),
+            row(1, 1.0),
+            row(12, 2.0),
+            row(3, 3.0),
+            row(25, 4.0),
+        ];
+        let (res, dropped) = run(rows, Reducer::Sum, 0);
+        assert_eq!(res.y[0].values, vec![1.0, 2.0, 4.0]);
+        assert_eq!(dropped, 1);
+    }
+
+    #[test]
+    fn clock_jumps() {
+        // single timestamp far ahead is dropped.
+        let rows = vec![header(), row(1, 1.0), row(1_000_000, 2.0), row(12, 3.0)];
+        let (res, dropped) = run(rows, Reducer::Sum, 0);
+        assert_eq!(res.y[0].values, vec![1.0, 3.0]);
+        assert_eq!(dropped, 1);
+
+        // jump confirmed by the next sample leaves one empty column.
+        let rows = vec![
+            header(),
+            row(1, 1.0),
+            row(1_000_000, 2.0),
+            row(1_000_001, 3.0),
+        ];
+        let (res, dropped) = run(rows, Reducer::Sum, 0);
+        assert_eq!(
+            res.t,
+            Some(vec![Some(0), Some(999_990), Some(1_000_000)].into())
+        );
+        assert!(res.y[0].values.get(1).unwrap().is_nan());
+        assert_eq!(res.y[0].values.get(2), Some(3.0));
+        assert_eq!(dropped, 1);
+    }
+
+    // set of one column, as pair input produces.
+    fn pair(title: &str, t: Timestamp, v: f64) -> ReaderMessage {
+        let mut series = Series::with_title(title);
+        series.values.push_back(v);
+        ReaderMessage::Extend(SeriesSet::new(
+            Some(("ts".to_owned(), vec![t.to_string()].into())),
+            Some(vec![Some(t)].into()),
+            vec![series],
+        ))
+    }
+
+    #[test]
+    fn titles_come_and_go() {
+        let res = resample(
+            vec![pair("a", 1, 1.0), pair("b", 12, 5.0), pair("a", 3, 3.0)],
+            Reducer::Sum,
+            10,
+        );
+        // late value of 'a' is still accepted after 'b' showed up.
+        let (a, b) = (series(&res, "a"), series(&res, "b"));
+        assert_eq!(a.values.get(0), Some(4.0));
+        assert!(a.values.get(1).unwrap().is_nan());
+        assert!(b.values.get(0).unwrap().is_nan());
+        assert_eq!(b.values.get(1), Some(5.0));
+    }
+
+    fn series<'a>(set: &'a SeriesSet, title: &str) -> &'a Series {
+        set.y.iter().find(|s| s.title == title).unwrap()
+    }
+
+    #[test]
+    fn columns_follow_sorted_series() {
+        // 'b' is sorted before 'a' when it shows up, columns of both
+        // still go to their own series.
+        let res = resample(
+            vec![
+                pair("a", 1, -1.0),
+                pair("a", 12, -2.0),
+                pair("b", 13, 5.0),
+                pair("a", 25, -3.0),
+            ],
+            Reducer::Sum,
+            0,
+        );
+        assert_eq!(res.y[0].title, "b");
+        assert_eq!(series(&res, "a").values, vec![-1.0, -2.0, -3.0]);
+        assert_eq!(series(&res, "b").values.get(1), Some(5.0));
+    }
+}
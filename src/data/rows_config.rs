@@ -0,0 +1,88 @@
+// max number of chart rows per series.
+pub const MAX_ROWS: usize = 8;
+
+/// RowsConfig tells how many screen rows the chart of each series takes,
+/// not counting the title row. Format is the same as for scales:
+/// rows,pattern:rows,... where series title must contain the pattern.
+/// First matching pattern is used; default is 1 row.
+#[derive(Debug, Clone)]
+pub struct RowsConfig {
+    entries: Vec<(String, usize)>,
+    default: usize,
+}
+
+impl Default for RowsConfig {
+    fn default() -> RowsConfig {
+        RowsConfig {
+            entries: vec![],
+            default: 1,
+        }
+    }
+}
+
+fn parse_rows(conf: &str) -> Result<usize, RowsError> {
+    match conf.parse::<usize>() {
+        Ok(n) if n > 0 && n <= MAX_ROWS => Ok(n),
+        _ => Err(RowsError::BadRows(conf.to_owned())),
+    }
+}
+
+impl RowsConfig {
+    pub fn new(conf: &str) -> Result<RowsConfig, RowsError> {
+        let mut res = RowsConfig::default();
+        for s in conf.split(',') {
+            let parts = s.split(':').collect::<Vec<&str>>();
+            match parts.len() {
+                1 => res.default = parse_rows(parts[0])?,
+                2 => res
+                    .entries
+                    .push((parts[0].to_owned(), parse_rows(parts[1])?)),
+                _ => return Err(RowsError::BadFormat(conf.to_owned())),
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn rows(&self, title: &str) -> usize {
+        self.entries
+            .iter()
+            .find(|(pattern, _)| title.contains(pattern))
+            .map_or(self.default, |(_, rows)| *rows)
+    }
+}
+
+#[derive(Debug)]
+pub enum RowsError {
+    BadRows(String),
+    BadFormat(String),
+}
+
+impl std::fmt::Display for RowsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            RowsError::BadRows(ref s) => {
+                write!(f, "Bad number of rows: {}, must be 1..{}", s, MAX_ROWS)
+            }
+            RowsError::BadFormat(ref s) => write!(f, "Bad Format: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_pick() {
+        let rows = RowsConfig::new("2,cpu:4,cpu_idle:1").unwrap();
+        assert_eq!(rows.rows("cpu_user"), 4);
+        // first match is used.
+        assert_eq!(rows.rows("cpu_idle"), 4);
+        assert_eq!(rows.rows("ram"), 2);
+        assert_eq!(RowsConfig::new("ram:3").unwrap().rows("cpu"), 1);
+
+        assert!(RowsConfig::new("0").is_err());
+        assert!(RowsConfig::new("cpu:9").is_err());
+        assert!(RowsConfig::new("a:b:c").is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::data::metric_parse::metric_parse;
+use crate::data::unit::Unit;
 use std::f64;
 
 #[derive(Debug, Copy, Clone)]
@@ -52,7 +53,7 @@ impl Scale {
 
     pub fn from_config(config: &str) -> Result<Scale, ScaleError> {
         let v: Result<Vec<f64>, std::num::ParseFloatError> =
-            config.split("..").map(|v| metric_parse(v)).collect();
+            config.split("..").map(metric_parse).collect();
         let v = v?;
         match v.len() {
             1 => Ok(Scale::new_positive(0.0, v[0])?),
@@ -62,19 +63,31 @@ impl Scale {
         }
     }
 
-    // Find 'decent' scale for a given list of values.
+    // Find 'decent' scale for given bounds of the values.
     // The logic is following:
     //  - if there's no data at all, return identity map;
     //  - if there's both negative and positive number, map [mn; 0; mx] -> [-1; 0; 1]
     //  - if only negative or positive number present, map [mn; 0] -> [-1; 0] OR [0; mx] -> [0; 1]
-    pub fn auto(v: &[f64]) -> Scale {
-        match min_max(v) {
+    fn from_bounds(bounds: Option<(f64, f64)>) -> Scale {
+        match bounds {
             // Error here would indicate a bug in a program, so we unwrap
             None => Scale::new(-1.0, 0.0, 1.0).unwrap(), // identity mapping
             Some((mn, mx)) => Scale::from_min_max(mn, mx).unwrap(),
         }
     }
 
+    // Default scale for the series with no scale configured.
+    // Percents which fit into [0; 100] are shown on that range,
+    // everything else is autoscaled.
+    pub fn auto_for_unit(bounds: Option<(f64, f64)>, unit: Option<&Unit>) -> Scale {
+        match (unit, bounds) {
+            (Some(Unit::Percent), Some((mn, mx))) if mn >= 0.0 && mx <= 100.0 => {
+                Scale::new_positive(0.0, 100.0).unwrap()
+            }
+            (_, bounds) => Scale::from_bounds(bounds),
+        }
+    }
+
     fn transform(from: (f64, f64), to: (f64, f64), v: f64) -> f64 {
         to.0 + (to.1 - to.0) * (v - from.0) / (from.1 - from.0)
     }
@@ -88,7 +101,7 @@ impl Scale {
     }
 
     #[allow(dead_code)]
-    pub fn to_tuple(&self) -> (f64, f64, f64) {
+    pub fn to_tuple(self) -> (f64, f64, f64) {
         (self.a, self.b, self.c)
     }
 }
@@ -107,10 +120,10 @@ pub struct Scales {
 impl Scales {
     // finds first one which matches the pattern
     pub fn pick(&self, title: &str) -> Option<Scale> {
-        match self.scales.iter().find(|&(p, _)| title.contains(p)) {
-            Some((_, scale)) => Some(*scale),
-            None => None,
-        }
+        self.scales
+            .iter()
+            .find(|&(p, _)| title.contains(p))
+            .map(|(_, scale)| *scale)
     }
 
     pub fn with_scales(scales: Vec<(String, Scale)>) -> Scales {
@@ -118,15 +131,6 @@ impl Scales {
     }
 }
 
-pub fn min_max(v: &[f64]) -> Option<(f64, f64)> {
-    v.iter()
-        .filter(|x| x.is_finite())
-        .fold(None, |acc, x| match acc {
-            None => Some((*x, *x)),
-            Some((mn, mx)) => Some((mn.min(*x), mx.max(*x))),
-        })
-}
-
 #[derive(Debug)]
 pub enum ScaleError {
     EmptyDomain(f64, f64, f64),
@@ -159,6 +163,11 @@ impl std::fmt::Display for ScaleError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::data::values::Values;
+
+    fn min_max(v: Vec<f64>) -> Option<(f64, f64)> {
+        Values::from(v).min_max()
+    }
     use assert_approx_eq::assert_approx_eq;
 
     #[test]
@@ -184,27 +193,41 @@ mod tests {
 
     #[test]
     fn scale_auto() {
-        let s = Scale::auto(&vec![-10.0, 20.0]);
+        let s = Scale::auto_for_unit(min_max(vec![-10.0, 20.0]), None);
         assert_approx_eq!(s.a, -10.0);
         assert_approx_eq!(s.b, 0.0);
         assert_approx_eq!(s.c, 20.0);
 
-        let s = Scale::auto(&vec![0.0]);
+        let s = Scale::auto_for_unit(min_max(vec![0.0]), None);
         assert_approx_eq!(s.a, -1.0);
         assert_approx_eq!(s.b, 0.0);
         assert_approx_eq!(s.c, 1.0);
 
-        let s = Scale::auto(&vec![]);
+        let s = Scale::auto_for_unit(min_max(vec![]), None);
         assert_approx_eq!(s.a, -1.0);
         assert_approx_eq!(s.b, 0.0);
         assert_approx_eq!(s.c, 1.0);
 
-        let s = Scale::auto(&vec![-10.0, 20.0, f64::INFINITY]);
+        let s = Scale::auto_for_unit(min_max(vec![-10.0, 20.0, f64::INFINITY]), None);
         assert_approx_eq!(s.a, -10.0);
         assert_approx_eq!(s.b, 0.0);
         assert_approx_eq!(s.c, 20.0);
     }
 
+    #[test]
+    fn scale_auto_for_unit() {
+        let s = Scale::auto_for_unit(min_max(vec![10.0, 20.0]), Some(&Unit::Percent));
+        assert_approx_eq!(s.b, 0.0);
+        assert_approx_eq!(s.c, 100.0);
+
+        // percents over 100 are autoscaled.
+        let s = Scale::auto_for_unit(min_max(vec![10.0, 400.0]), Some(&Unit::Percent));
+        assert_approx_eq!(s.c, 400.0);
+
+        let s = Scale::auto_for_unit(min_max(vec![10.0, 20.0]), Some(&Unit::Bytes));
+        assert_approx_eq!(s.c, 20.0);
+    }
+
     #[test]
     fn parse_one_scale_and_run() {
         // maps [0; 10k] -> [0; 1]
@@ -243,20 +266,20 @@ mod tests {
 
     #[test]
     fn min_max_test() {
-        let t = min_max(&vec![0.0, 1.0, 2.0]);
+        let t = min_max(vec![0.0, 1.0, 2.0]);
         assert_approx_eq!(t.unwrap().0, 0.0);
         assert_approx_eq!(t.unwrap().1, 2.0);
 
-        let t = min_max(&vec![0.0]);
+        let t = min_max(vec![0.0]);
         assert_approx_eq!(t.unwrap().0, 0.0);
         assert_approx_eq!(t.unwrap().1, 0.0);
 
-        assert_eq!(min_max(&vec![]), None);
-        assert_eq!(min_max(&vec![f64::INFINITY]), None);
-        assert_eq!(min_max(&vec![f64::NEG_INFINITY]), None);
-        assert_eq!(min_max(&vec![f64::NAN]), None);
+        assert_eq!(min_max(vec![]), None);
+        assert_eq!(min_max(vec![f64::INFINITY]), None);
+        assert_eq!(min_max(vec![f64::NEG_INFINITY]), None);
+        assert_eq!(min_max(vec![f64::NAN]), None);
 
-        let t = min_max(&vec![0.0, f64::NAN]);
+        let t = min_max(vec![0.0, f64::NAN]);
         assert_approx_eq!(t.unwrap().0, 0.0);
         assert_approx_eq!(t.unwrap().1, 0.0);
     }
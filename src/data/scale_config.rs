@@ -1,5 +1,6 @@
-use crate::data::scale::{min_max, Scale, ScaleError, Scales};
+use crate::data::scale::{Scale, ScaleError, Scales};
 use crate::data::series::Series;
+use crate::data::values::Summary;
 
 use std::collections::HashMap;
 use std::f64;
@@ -65,16 +66,16 @@ impl ScalesConfig {
 
                 match parts.len() {
                     1 => {
-                        wildcard = Some(ScaleConfig::new("", &parts[0])?);
+                        wildcard = Some(ScaleConfig::new("", parts[0])?);
                         Ok(None)
                     }
-                    2 => Ok(Some(ScaleConfig::new(&parts[0], &parts[1])?)),
+                    2 => Ok(Some(ScaleConfig::new(parts[0], parts[1])?)),
                     _ => Err(ScaleError::BadFormat(conf.to_owned())),
                 }
             })
             .collect();
 
-        let mut res: Vec<ScaleConfig> = scales?.into_iter().filter_map(|v| v).collect();
+        let mut res: Vec<ScaleConfig> = scales?.into_iter().flatten().collect();
 
         // wildcard is last
         if let Some(v) = wildcard {
@@ -91,31 +92,25 @@ impl ScalesConfig {
         self.entries
             .iter()
             .find(|c| title.contains(&c.pattern))
-            .filter(|c| match c.config {
-                ScaleType::Auto => true,
-                _ => false,
-            })
+            .filter(|c| matches!(c.config, ScaleType::Auto))
     }
 
     // for each autoscale config, computes min/max values in the data.
+    // Series keep their summaries up to date, so this doesn't
+    // scan the values.
     fn bounds(&self, series: &[Series]) -> HashMap<String, (f64, f64)> {
-        let mut bounds = HashMap::new();
+        let mut groups: HashMap<String, Summary> = HashMap::new();
         series.iter().for_each(|s| {
             // if it's autoscale matching the series
             if let Some(scale_config) = self.find_auto(&s.title) {
-                // and if there's valid min and max values for the series
-                if let Some((mn, mx)) = min_max(&s.values) {
-                    bounds
-                        .entry(scale_config.pattern.clone())
-                        .and_modify(|v: &mut (f64, f64)| {
-                            v.0 = v.0.min(mn);
-                            v.1 = v.1.max(mx);
-                        })
-                        .or_insert((mn, mx));
-                }
+                let group = groups.entry(scale_config.pattern.clone()).or_default();
+                *group = group.merge(s.values.summary());
             }
         });
-        bounds
+        groups
+            .into_iter()
+            .filter_map(|(pattern, summary)| summary.bounds().map(|b| (pattern, b)))
+            .collect()
     }
 
     // Transforms autoscaling groups to completely defined scales
@@ -129,6 +124,7 @@ impl ScalesConfig {
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::*;
     use assert_approx_eq::assert_approx_eq;
@@ -182,15 +178,18 @@ mod tests {
         let s = vec![
             Series {
                 title: "cpu1".to_owned(),
-                values: vec![0.0, 10.0, 11.0, 9.0],
+                values: vec![0.0, 10.0, 11.0, 9.0].into(),
+                unit: None,
             },
             Series {
                 title: "cpu2".to_owned(),
-                values: vec![0.0, 100.0, 11.0, 99.0],
+                values: vec![0.0, 100.0, 11.0, 99.0].into(),
+                unit: None,
             },
             Series {
                 title: "ram_free_mb".to_owned(),
-                values: vec![1111.0, 999.0, 888.0, 99.0],
+                values: vec![1111.0, 999.0, 888.0, 99.0].into(),
+                unit: None,
             },
         ];
         let scales = ScalesConfig::new("cpu:auto").unwrap();
@@ -214,15 +213,18 @@ mod tests {
         let s = vec![
             Series {
                 title: "cpu1".to_owned(),
-                values: vec![0.0, 10.0, 11.0, 9.0],
+                values: vec![0.0, 10.0, 11.0, 9.0].into(),
+                unit: None,
             },
             Series {
                 title: "cpu2".to_owned(),
-                values: vec![0.0, 100.0, 11.0, 99.0],
+                values: vec![0.0, 100.0, 11.0, 99.0].into(),
+                unit: None,
             },
             Series {
                 title: "ram_free_mb".to_owned(),
-                values: vec![1111.0, 999.0, 888.0, 99.0],
+                values: vec![1111.0, 999.0, 888.0, 99.0].into(),
+                unit: None,
             },
         ];
         let scales = ScalesConfig::new("auto").unwrap();
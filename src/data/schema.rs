@@ -1,5 +1,10 @@
-use crate::app::settings::Column;
+use crate::app::settings::{Column, Header};
 use crate::data::series::{Series, SeriesSet, Slice};
+use crate::data::time::TimeParser;
+use crate::data::unit::{parse_unit, parse_value, unit_from_title, Unit};
+
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 struct ColumnSchema {
     title: String,
@@ -12,6 +17,29 @@ impl ColumnSchema {
     }
 }
 
+/// SchemaSettings is the part of user settings which defines
+/// how schema is built from the titles.
+#[derive(Clone)]
+pub struct SchemaSettings {
+    pub x: Column,
+    pub header: Header,
+    // if set, x values are parsed as timestamps.
+    pub time: Option<TimeParser>,
+    // if set, only series with matching titles are kept.
+    pub only: Option<Regex>,
+    // if set, series with matching titles are dropped.
+    pub drop: Option<Regex>,
+    // old title -> new title, to keep the history of renamed series.
+    pub aliases: HashMap<String, String>,
+}
+
+impl SchemaSettings {
+    fn keeps(&self, title: &str) -> bool {
+        self.only.as_ref().is_none_or(|r| r.is_match(title))
+            && !self.drop.as_ref().is_some_and(|r| r.is_match(title))
+    }
+}
+
 /// Schema represents the way input data is transformed to internal format.
 /// At the moment, schema has only the definition of two special fields: X and Epoch.
 /// Every other field from the input will become a data series.
@@ -19,88 +47,286 @@ pub struct Schema {
     x: Option<ColumnSchema>,
     // titles should be also stored here.
     titles: Vec<String>,
+    // input columns which are not stored at all.
+    dropped: Vec<bool>,
+    time: Option<TimeParser>,
+    // units detected in the values so far.
+    units: Vec<Option<Unit>>,
+    // multipliers to base units, declared by the title suffix or the
+    // header units row; values are kept in the declared units.
+    declared: Vec<f64>,
+    // units changed, but were not reported yet.
+    units_changed: bool,
+    // any data rows were formatted already.
+    has_rows: bool,
+    // cells which failed to parse since last check: index and series title.
+    failures: Vec<(usize, String)>,
+    // series which had at least one valid value.
+    with_data: Vec<bool>,
 }
 
-impl Schema {
-    // parse string here?
+// Duplicate titles get a suffix: a, a#2, a#3, ...
+fn unique_title(used: &mut HashSet<String>, title: &str) -> String {
+    let mut res = title.to_owned();
+    let mut k = 1;
+    while used.contains(&res) {
+        k += 1;
+        res = format!("{}#{}", title, k);
+    }
+    used.insert(res.clone());
+    res
+}
 
-    fn default() -> Schema {
+impl Schema {
+    fn new(settings: &SchemaSettings) -> Schema {
         Schema {
             x: None,
             titles: vec![],
+            dropped: vec![],
+            time: settings.time.clone(),
+            units: vec![],
+            declared: vec![],
+            units_changed: false,
+            has_rows: false,
+            failures: vec![],
+            with_data: vec![],
         }
     }
 
-    pub fn from_title_range(x: Column, titles: &[String]) -> Schema {
-        let mut res = Schema::default();
+    fn with_title_units(mut self) -> Schema {
+        let declared: Vec<(f64, Option<Unit>)> = self
+            .titles
+            .iter()
+            .map(|t| match unit_from_title(t) {
+                Some((m, u)) => (m, Some(u)),
+                None => (1.0, None),
+            })
+            .collect();
+        self.declare(declared);
+        self
+    }
 
-        titles.iter().zip(0..).for_each(|(t, i)| {
-            if x.matches(t, i) {
+    fn declare(&mut self, declared: Vec<(f64, Option<Unit>)>) {
+        self.declared = declared.iter().map(|(m, _)| *m).collect();
+        self.units = declared
+            .into_iter()
+            .map(|(m, u)| Unit::declared(m, u))
+            .collect();
+    }
+
+    fn from_iter<'a>(settings: &SchemaSettings, titles: impl Iterator<Item = &'a str>) -> Schema {
+        let mut res = Schema::new(settings);
+        let mut used = HashSet::new();
+
+        titles.zip(0..).for_each(|(t, i)| {
+            if settings.x.matches(t, i) {
+                res.dropped.push(false);
                 res.x = Some(ColumnSchema::new(t.to_owned(), i));
-            } else {
-                res.titles.push(t.to_owned());
+                return;
+            }
+            let t = settings.aliases.get(t).map_or(t, String::as_str);
+            let keep = settings.keeps(t);
+            res.dropped.push(!keep);
+            if keep {
+                let t = unique_title(&mut used, t);
+                res.titles.push(t);
             }
         });
+        res.with_data = vec![false; res.titles.len()];
 
-        res
+        res.with_title_units()
     }
 
-    pub fn from_titles(x: Column, titles: &str) -> Schema {
-        let mut res = Schema::default();
+    pub fn from_title_range(settings: &SchemaSettings, titles: &[String]) -> Schema {
+        Schema::from_iter(settings, titles.iter().map(String::as_str))
+    }
 
-        titles.split(',').zip(0..).for_each(|(t, i)| {
-            if x.matches(t, i) {
-                res.x = Some(ColumnSchema::new(t.to_owned(), i));
-            } else {
-                res.titles.push(t.to_owned());
-            }
-        });
+    pub fn from_titles(settings: &SchemaSettings, titles: &str) -> Schema {
+        Schema::from_iter(settings, titles.split(','))
+    }
 
-        res
+    pub fn titles(&self) -> &[String] {
+        &self.titles
+    }
+
+    // titles of the series which had at least one valid value.
+    pub fn titles_with_data(&self) -> Vec<String> {
+        self.titles
+            .iter()
+            .zip(self.with_data.iter())
+            .filter(|(_, &d)| d)
+            .map(|(t, _)| t.clone())
+            .collect()
+    }
+
+    fn is_dropped(&self, index: usize) -> bool {
+        self.dropped.get(index).cloned().unwrap_or(false)
+    }
+
+    /// Builds the schema for the first line of the block, if that line is
+    /// data rather than a header. Titles are taken from settings, and
+    /// generated as c1..cN for the columns not covered.
+    pub fn headerless(settings: &SchemaSettings, line: &str) -> Option<Schema> {
+        let given = match &settings.header {
+            Header::Present => return None,
+            Header::Absent(given) => given.as_deref().unwrap_or(&[]),
+            Header::Auto => {
+                let numeric = line
+                    .split(',')
+                    .enumerate()
+                    .filter(|(i, _)| !matches!(settings.x, Column::Index(x) if x == *i))
+                    .all(|(_, v)| parse_value(v).is_some());
+                if !numeric {
+                    return None;
+                }
+                &[]
+            }
+        };
+        let titles: Vec<String> = (0..line.split(',').count())
+            .map(|i| match given.get(i) {
+                Some(t) => t.clone(),
+                None => format!("c{}", i + 1),
+            })
+            .collect();
+        Some(Schema::from_title_range(settings, &titles))
     }
 
     /// Returns a stub of SeriesSet, with correct number of
     /// empty series.
     pub fn empty_set(&self) -> SeriesSet {
-        SeriesSet {
-            x: match self.x {
-                Some(ref x) => Some((x.title.clone(), vec![])),
-                _ => None,
-            },
-            y: self.titles.iter().map(|t| Series::with_title(t)).collect(),
-        }
+        SeriesSet::new(
+            self.x.as_ref().map(|x| (x.title.clone(), VecDeque::new())),
+            self.x
+                .as_ref()
+                .and(self.time.as_ref())
+                .map(|_| VecDeque::new()),
+            self.titles
+                .iter()
+                .zip(self.units.iter())
+                .map(|(t, u)| Series {
+                    unit: u.clone(),
+                    ..Series::with_title(t)
+                })
+                .collect(),
+        )
     }
 
-    /// Formats a row of input data as a slice.
-    /// Slice can be appended to a SeriesSet.
-    pub fn slice(&self, slice: &str) -> Slice {
-        let mut res = Slice::default();
-        slice
+    /// Header might be followed by a row with units, e.g. 'time,ms,KiB,%'.
+    /// Row is accepted as units row only if it goes right after the header,
+    /// none of the cells is a number, and at least one of them is a unit.
+    pub fn units_row(&mut self, row: &str) -> bool {
+        if self.has_rows {
+            return false;
+        }
+        let cells: Vec<&str> = row
             .split(',')
             .enumerate()
-            .for_each(|(i, v)| match &self.x {
-                Some(x) if x.index == i => res.x = Some(v.to_owned()),
-                _ => res.y.push(v.trim().parse::<f64>().unwrap_or(std::f64::NAN)),
-            });
-        res
+            .filter(|(i, _)| self.x.as_ref().is_none_or(|x| x.index != *i) && !self.is_dropped(*i))
+            .map(|(_, c)| c)
+            .collect();
+        if cells.iter().any(|c| parse_value(c).is_some()) {
+            return false;
+        }
+        let declared: Option<Vec<(f64, Option<Unit>)>> =
+            cells.iter().map(|c| parse_unit(c)).collect();
+        match declared {
+            Some(d) if d.iter().any(|(m, u)| u.is_some() || *m != 1.0) => {
+                self.declare(d);
+                self.units_changed = true;
+                true
+            }
+            _ => false,
+        }
     }
 
-    pub fn slice_from_range(&self, slice: &[String]) -> Slice {
+    fn set_x(&self, res: &mut Slice, v: &str) {
+        res.t = self.time.as_ref().map(|p| p.parse(v));
+        res.x = Some(v.to_owned());
+    }
+
+    fn slice_from_iter<'a>(&mut self, values: impl Iterator<Item = &'a str>) -> Slice {
         let mut res = Slice::default();
-        slice.iter().enumerate().for_each(|(i, v)| match &self.x {
-            Some(x) if x.index == i => res.x = Some(v.to_owned()),
-            _ => res.y.push(v.trim().parse::<f64>().unwrap_or(std::f64::NAN)),
+        let mut units_changed = std::mem::replace(&mut self.units_changed, false);
+        self.has_rows = true;
+        values.enumerate().for_each(|(i, v)| match &self.x {
+            Some(x) if x.index == i => self.set_x(&mut res, v),
+            _ if self.is_dropped(i) => {}
+            _ => {
+                let declared = self.declared.get(res.y.len()).cloned().unwrap_or(1.0);
+                let value = match parse_value(v) {
+                    // values with a suffix are converted to the declared units.
+                    Some((value, Some(_))) if declared != 1.0 => value / declared,
+                    Some((value, Some(unit))) => {
+                        let j = res.y.len();
+                        if self.units.len() <= j {
+                            self.units.resize(j + 1, None);
+                        }
+                        if self.units[j].as_ref() != Some(&unit) {
+                            self.units[j] = Some(unit);
+                            units_changed = true;
+                        }
+                        value
+                    }
+                    Some((value, None)) => value,
+                    None => {
+                        // empty cells are missing values, not failures.
+                        if let (false, Some(title)) =
+                            (v.trim().is_empty(), self.titles.get(res.y.len()))
+                        {
+                            self.failures.push((i, title.clone()));
+                        }
+                        f64::NAN
+                    }
+                };
+                if !value.is_nan() {
+                    if let Some(d) = self.with_data.get_mut(res.y.len()) {
+                        *d = true;
+                    }
+                }
+                res.y.push(value);
+            }
         });
+        if units_changed {
+            res.units = Some(self.units.clone());
+        }
         res
     }
+
+    /// Returns cells which failed to parse since the last call,
+    /// as index in the row and series title.
+    pub fn take_failures(&mut self) -> Vec<(usize, String)> {
+        std::mem::take(&mut self.failures)
+    }
+
+    /// Formats a row of input data as a slice.
+    /// Slice can be appended to a SeriesSet.
+    pub fn slice(&mut self, slice: &str) -> Slice {
+        self.slice_from_iter(slice.split(','))
+    }
+
+    pub fn slice_from_range(&mut self, slice: &[String]) -> Slice {
+        self.slice_from_iter(slice.iter().map(String::as_str))
+    }
 }
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::data::time::{TimeFormat, Zone};
+
+    fn settings(x: Column) -> SchemaSettings {
+        SchemaSettings {
+            x,
+            header: Header::Auto,
+            time: None,
+            only: None,
+            drop: None,
+            aliases: HashMap::new(),
+        }
+    }
 
     #[test]
     fn test_schema() {
-        let schema = Schema::from_titles(Column::None, "a,b,c");
+        let mut schema = Schema::from_titles(&settings(Column::None), "a,b,c");
         let s = schema.empty_set();
         assert_eq!(s.x, None);
         assert_eq!(s.y.len(), 3);
@@ -115,9 +341,9 @@ mod tests {
 
     #[test]
     fn test_x() {
-        let schema = Schema::from_titles(Column::Index(0), "a,b,c");
+        let mut schema = Schema::from_titles(&settings(Column::Index(0)), "a,b,c");
         let s = schema.empty_set();
-        assert_eq!(s.x, Some(("a".to_owned(), vec![])));
+        assert_eq!(s.x, Some(("a".to_owned(), VecDeque::new())));
         assert_eq!(s.y.len(), 2);
         assert_eq!(s.y[0].title, "b");
 
@@ -125,4 +351,119 @@ mod tests {
         assert_eq!(slice.x, Some("1".to_owned()));
         assert_eq!(slice.y, vec![2.0, 3.0]);
     }
+
+    #[test]
+    fn test_time() {
+        let settings = SchemaSettings {
+            x: Column::Title("ts".to_owned()),
+            header: Header::Auto,
+            time: Some(TimeParser::new(TimeFormat::EpochSeconds, Zone::Utc)),
+            only: None,
+            drop: None,
+            aliases: HashMap::new(),
+        };
+        let mut schema = Schema::from_titles(&settings, "ts,a");
+        assert_eq!(schema.empty_set().t, Some(VecDeque::new()));
+
+        let slice = schema.slice("10,1");
+        assert_eq!(slice.x, Some("10".to_owned()));
+        assert_eq!(slice.t, Some(Some(10_000_000_000)));
+        assert_eq!(schema.slice("now,1").t, Some(None));
+    }
+
+    #[test]
+    fn test_units() {
+        let mut schema = Schema::from_titles(&settings(Column::None), "a,b,c");
+        let slice = schema.slice("1K,250ms,x");
+        assert_eq!(slice.y[0..2], [1000.0, 0.25]);
+        assert!(slice.y[2].is_nan());
+        assert_eq!(schema.take_failures(), vec![(2, "c".to_owned())]);
+        assert!(schema.slice("1,,3").y[1].is_nan());
+        assert!(schema.take_failures().is_empty());
+        assert_eq!(slice.units, Some(vec![None, Some(Unit::Seconds), None]));
+
+        // units are only reported when they change
+        assert_eq!(schema.slice("2K,1s,3").units, None);
+        let mut set = schema.empty_set();
+        set.append_slice(schema.slice("1,2,3%"));
+        assert_eq!(set.y[1].unit, Some(Unit::Seconds));
+        assert_eq!(set.y[2].unit, Some(Unit::Percent));
+    }
+
+    #[test]
+    fn test_declared_units() {
+        let mut schema = Schema::from_titles(&settings(Column::None), "rss_bytes,p99_ms,n");
+        let s = schema.empty_set();
+        assert_eq!(s.y[0].unit, Some(Unit::Bytes));
+        assert_eq!(s.y[1].unit, Unit::declared(1.0e-3, Some(Unit::Seconds)));
+        assert_eq!(s.y[2].unit, None);
+        // values are kept in the declared units.
+        assert_eq!(schema.slice("1,250,3").y, vec![1.0, 250.0, 3.0]);
+        assert_eq!(schema.slice("1K,1s,3").y, vec![1000.0, 1000.0, 3.0]);
+        // units row is only accepted right after the header
+        assert!(!schema.units_row("B,s,"));
+
+        let mut schema = Schema::from_titles(&settings(Column::Index(0)), "ts,a,b,c");
+        assert!(!schema.units_row("1,2,3,4"));
+        assert!(!schema.units_row("ts,a,b,c"));
+        assert!(schema.units_row("time,KiB,[ms],"));
+        let slice = schema.slice("1,2,3,4");
+        assert_eq!(slice.y, vec![2.0, 3.0, 4.0]);
+        assert_eq!(
+            slice.units,
+            Some(vec![
+                Unit::declared(1024.0, Some(Unit::Bytes)),
+                Unit::declared(1.0e-3, Some(Unit::Seconds)),
+                None
+            ])
+        );
+    }
+
+    #[test]
+    fn test_headerless() {
+        assert!(Schema::headerless(&settings(Column::None), "a,b").is_none());
+        assert!(Schema::headerless(&settings(Column::None), "1,x").is_none());
+        let schema = Schema::headerless(&settings(Column::Index(0)), "12:00,1,2K").unwrap();
+        let s = schema.empty_set();
+        assert_eq!(s.x, Some(("c1".to_owned(), VecDeque::new())));
+        assert_eq!(s.y[1].title, "c3");
+
+        let mut settings = settings(Column::None);
+        settings.header = Header::Absent(Some(vec!["a".to_owned()]));
+        let s = Schema::headerless(&settings, "x,y").unwrap().empty_set();
+        assert_eq!(s.y[0].title, "a");
+        assert_eq!(s.y[1].title, "c2");
+
+        settings.header = Header::Present;
+        assert!(Schema::headerless(&settings, "1,2").is_none());
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut settings = settings(Column::Title("ts".to_owned()));
+        settings.only = Some(Regex::new("^(ts|cpu|st)").unwrap());
+        settings.drop = Some(Regex::new("^st$").unwrap());
+        let mut schema = Schema::from_titles(&settings, "ts,cpu1,st,ram,cpu2");
+        let s = schema.empty_set();
+        assert_eq!(s.x, Some(("ts".to_owned(), VecDeque::new())));
+        let titles: Vec<&str> = s.y.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, vec!["cpu1", "cpu2"]);
+
+        let slice = schema.slice("10,1,x,3,4");
+        assert_eq!(slice.x, Some("10".to_owned()));
+        assert_eq!(slice.y, vec![1.0, 4.0]);
+        // dropped columns are not even parsed.
+        assert!(schema.take_failures().is_empty());
+    }
+
+    #[test]
+    fn test_duplicates_and_aliases() {
+        let mut settings = settings(Column::None);
+        settings.aliases.insert("old".to_owned(), "new".to_owned());
+        let mut schema = Schema::from_titles(&settings, "a,old,a,a#2,a");
+        assert_eq!(schema.titles(), ["a", "new", "a#2", "a#2#2", "a#3"]);
+
+        schema.slice("1,,x,4,");
+        assert_eq!(schema.titles_with_data(), vec!["a", "a#2#2"]);
+    }
 }
@@ -1,109 +1,345 @@
-use std::f64::NAN;
+use std::collections::{HashMap, VecDeque};
 use std::iter;
 
 use crate::app::settings::SortingMode;
+use crate::data::time::{parse_duration, TimeError, Timestamp};
+use crate::data::unit::Unit;
+use crate::data::values::Values;
 
 #[derive(Debug, Clone)]
 pub struct Series {
     pub title: String,
-    pub values: Vec<f64>,
+    // compressed, old values can be evicted cheaply.
+    pub values: Values,
+    pub unit: Option<Unit>,
 }
 
 impl Series {
     pub fn with_title(title: &str) -> Series {
         Series {
             title: title.to_string(),
-            values: vec![],
+            values: Values::new(),
+            unit: None,
         }
     }
 }
 
 pub struct Slice {
     pub x: Option<String>,
+    // parsed x value, if x is configured to be a timestamp.
+    pub t: Option<Option<Timestamp>>,
     pub y: Vec<f64>,
+    // units of the series, only present if they changed.
+    pub units: Option<Vec<Option<Unit>>>,
 }
 
 impl Slice {
     pub fn default() -> Slice {
-        Slice { x: None, y: vec![] }
+        Slice {
+            x: None,
+            t: None,
+            y: vec![],
+            units: None,
+        }
+    }
+}
+
+// How much history to keep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Retention {
+    Columns(usize),
+    // relative to the latest timestamp; requires timestamps.
+    Duration(Timestamp),
+}
+
+impl Retention {
+    // either number of columns, e.g. 10000, or duration, e.g. 24h.
+    pub fn new(conf: &str) -> Result<Retention, TimeError> {
+        match conf.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(Retention::Columns(n)),
+            _ => parse_duration(conf).map(Retention::Duration),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct SeriesSet {
-    pub x: Option<(String, Vec<String>)>,
+    pub x: Option<(String, VecDeque<String>)>,
+    // timestamps for x values; None for values which failed to parse.
+    pub t: Option<VecDeque<Option<Timestamp>>>,
     pub y: Vec<Series>,
+    // position of each series in y by title.
+    index: HashMap<String, usize>,
+    // position in y for every value of a slice. Slices follow the
+    // titles of the latest appended set, while y might be sorted.
+    layout: Vec<usize>,
+    // sort keys of y as of the latest sorting.
+    keys: Vec<i64>,
+}
+
+fn nans(n: usize) -> impl Iterator<Item = f64> {
+    iter::repeat_n(f64::NAN, n)
 }
 
 impl SeriesSet {
+    pub fn new(
+        x: Option<(String, VecDeque<String>)>,
+        t: Option<VecDeque<Option<Timestamp>>>,
+        y: Vec<Series>,
+    ) -> SeriesSet {
+        let layout = (0..y.len()).collect();
+        let mut set = SeriesSet {
+            x,
+            t,
+            y,
+            index: HashMap::new(),
+            layout,
+            keys: vec![],
+        };
+        set.reindex();
+        set
+    }
+
     pub fn default() -> SeriesSet {
-        SeriesSet { y: vec![], x: None }
+        SeriesSet::new(None, None, vec![])
+    }
+
+    fn reindex(&mut self) {
+        self.index = self
+            .y
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.title.clone(), i))
+            .collect();
     }
 
     pub fn series_size(&self) -> i64 {
         self.y.first().map(|s| s.values.len() as i64).unwrap_or(0)
     }
 
-    pub fn series_count(&self) -> i64 {
-        self.y.len() as i64
+    // values of each column; every series is decompressed once.
+    pub fn columns(&self) -> impl Iterator<Item = Vec<f64>> + '_ {
+        let mut values: Vec<_> = self.y.iter().map(|s| s.values.iter()).collect();
+        (0..self.series_size()).map(move |_| {
+            values
+                .iter_mut()
+                .map(|v| v.next().unwrap_or(f64::NAN))
+                .collect()
+        })
     }
 
     pub fn append_slice(&mut self, slice: Slice) {
         if let (Some((_, x)), Some(xn)) = (self.x.as_mut(), slice.x.as_ref()) {
-            x.push(xn.to_owned());
+            x.push_back(xn.to_owned());
+        }
+        if let (Some(t), Some(tn)) = (self.t.as_mut(), slice.t) {
+            t.push_back(tn);
+        }
+        // y might have been modified directly.
+        if self.layout.iter().any(|&i| i >= self.y.len()) {
+            self.layout = (0..self.y.len()).collect();
         }
-        // here we pad the slice with 0 (should be NaN?) if it's shorter
+        if let Some(units) = slice.units {
+            let y = &mut self.y;
+            self.layout
+                .iter()
+                .zip(units)
+                .filter(|(_, u)| u.is_some())
+                .for_each(|(&i, u)| y[i].unit = u);
+        }
+        let mut used = vec![false; self.y.len()];
+        let y = &mut self.y;
+        self.layout.iter().zip(slice.y).for_each(|(&i, v)| {
+            used[i] = true;
+            y[i].values.push_back(v);
+        });
+        // series not in the slice are padded with NaN.
         self.y
             .iter_mut()
-            .zip(slice.y.iter().chain(iter::repeat(&NAN)))
-            .for_each(|(y, v)| y.values.push(*v));
+            .zip(used)
+            .filter(|(_, used)| !used)
+            .for_each(|(s, _)| s.values.push_back(f64::NAN));
     }
 
-    pub fn append_set(&mut self, mut other: SeriesSet) {
-        let old_length = self.series_size();
-        let new_length = other.series_size();
-        let mut used = vec![false; self.series_count() as usize];
+    // Appends the set, matching series by title. Known series are
+    // extended in place, new ones are added at the end and padded
+    // with NaN for the columns before them.
+    pub fn append_set(&mut self, other: SeriesSet) {
+        let old_length = self.series_size() as usize;
+        let new_length = other.series_size() as usize;
+        // y might have been modified directly.
+        if self.index.len() != self.y.len() {
+            self.reindex();
+        }
+        let mut used = vec![false; self.y.len()];
 
-        other.y.iter_mut().for_each(|ns| {
-            let mut all_values = if let Some((idx, old_series)) = self
-                .y
-                .iter_mut()
-                .enumerate()
-                .find(|(_, os)| os.title == ns.title)
-            {
-                used[idx] = true;
-                let mut new_series = vec![];
-                new_series.append(&mut old_series.values);
-                new_series
-            } else {
-                vec![NAN; old_length as usize]
-            };
-            all_values.append(&mut ns.values);
-            ns.values = all_values;
-        });
+        self.layout = other
+            .y
+            .into_iter()
+            .map(|mut ns| match self.index.get(&ns.title) {
+                Some(&i) => {
+                    let old_series = &mut self.y[i];
+                    used[i] = true;
+                    if ns.unit.is_some() {
+                        old_series.unit = ns.unit;
+                    }
+                    old_series.values.append(&mut ns.values);
+                    i
+                }
+                None => {
+                    let mut values: Values = nans(old_length).collect();
+                    values.append(&mut ns.values);
+                    ns.values = values;
+                    let i = self.y.len();
+                    self.index.insert(ns.title.clone(), i);
+                    self.y.push(ns);
+                    i
+                }
+            })
+            .collect();
 
-        let mut i = 0;
-        self.y.retain(|_| !(used[i], i += 1).0);
+        self.y
+            .iter_mut()
+            .zip(used)
+            .filter(|(_, used)| !used)
+            .for_each(|(os, _)| os.values.extend(nans(new_length)));
 
-        self.y.iter_mut().for_each(|os| {
-            os.values.append(&mut vec![NAN; new_length as usize]);
-        });
+        // x might be missing in the old set, if it is empty, or
+        // if x column was not present in previous headers.
+        if let Some((title, mut xn)) = other.x {
+            let (_, xo) = self.x.get_or_insert_with(|| {
+                (title, iter::repeat_n(String::new(), old_length).collect())
+            });
+            xo.append(&mut xn);
+        }
+        if let Some(mut tn) = other.t {
+            self.t
+                .get_or_insert_with(|| iter::repeat_n(None, old_length).collect())
+                .append(&mut tn);
+        }
+    }
 
-        other.y.append(&mut self.y);
-        self.y = other.y;
+    // timestamp for the column, if available.
+    pub fn time_at(&self, index: usize) -> Option<Timestamp> {
+        self.t
+            .as_ref()
+            .and_then(|t| t.get(index).cloned().flatten())
+    }
 
-        if let (Some((_, xo)), Some((_, xn))) = (self.x.as_mut(), other.x.as_mut()) {
-            xo.append(xn);
+    // Finds the column which is 'duration' away from the column 'from'.
+    // Timestamps are not required to be sorted, so it's a linear scan,
+    // stopping at the first column which is far enough.
+    pub fn index_after(&self, from: usize, duration: Timestamp) -> Option<usize> {
+        let t = self.t.as_ref()?;
+        let start = self.time_at(from)?;
+        if duration >= 0 {
+            (from..t.len()).find(|&i| t[i].is_some_and(|ti| ti >= start + duration))
+        } else {
+            (0..=from)
+                .rev()
+                .find(|&i| t[i].is_some_and(|ti| ti <= start + duration))
+        }
+    }
+
+    // Removes the oldest columns which are out of retention.
+    // Returns number of columns removed.
+    pub fn evict(&mut self, retention: Retention) -> usize {
+        let size = self.series_size() as usize;
+        let n = match retention {
+            Retention::Columns(keep) => size.saturating_sub(keep),
+            Retention::Duration(keep) => match self.t.as_ref() {
+                Some(t) => match t.iter().rev().find_map(|&t| t) {
+                    // columns without timestamp are evicted along with old ones.
+                    Some(latest) => t
+                        .iter()
+                        .take_while(|t| t.is_none_or(|t| t < latest - keep))
+                        .count()
+                        .min(size),
+                    None => 0,
+                },
+                None => 0,
+            },
+        };
+        if n == 0 {
+            return 0;
+        }
+        self.y.iter_mut().for_each(|s| {
+            s.values.evict(n);
+        });
+        if let Some((_, x)) = self.x.as_mut() {
+            x.drain(..n.min(x.len()));
         }
+        if let Some(t) = self.t.as_mut() {
+            t.drain(..n.min(t.len()));
+        }
+        n
     }
 
+    // Sorts series by the mode. Series which kept their key since the
+    // previous sorting are still in order, so when only a few keys
+    // changed, these series are moved into place one by one. Only the
+    // range of y which actually changed is rearranged and reindexed,
+    // and layout follows the series to their new places.
     pub fn order_by(&mut self, mode: &SortingMode) {
-        match mode {
-            SortingMode::ValuesDesc => self.y.sort_by_cached_key(|a| {
-                -(a.values.iter().filter(|v| !v.is_nan()).sum::<f64>() * 1.0e9) as i64
-            }),
-            SortingMode::TitlesNumericAsc => {
-                self.y.sort_by_key(|a| a.title.parse::<i64>().unwrap_or(0))
+        let key = |s: &Series| match mode {
+            SortingMode::ValuesDesc => -(s.values.sum() * 1.0e9) as i64,
+            SortingMode::TitlesNumericAsc => s.title.parse::<i64>().unwrap_or(0),
+        };
+        let keys: Vec<i64> = self.y.iter().map(key).collect();
+        let moved: Vec<usize> = (0..keys.len())
+            .filter(|&i| self.keys.get(i) != Some(&keys[i]))
+            .collect();
+
+        // order[new] is the old position of the series.
+        let order: Vec<usize> = if moved.len() * moved.len() > keys.len() {
+            // stable sort is linear for series which are mostly in order.
+            let mut order: Vec<usize> = (0..keys.len()).collect();
+            order.sort_by_key(|&i| keys[i]);
+            order
+        } else {
+            let mut stays = vec![true; keys.len()];
+            moved.iter().for_each(|&i| stays[i] = false);
+            let mut order: Vec<usize> = (0..keys.len()).filter(|&i| stays[i]).collect();
+            moved.iter().for_each(|&i| {
+                let at = order.partition_point(|&j| keys[j] <= keys[i]);
+                order.insert(at, i);
+            });
+            order
+        };
+        self.keys = order.iter().map(|&i| keys[i]).collect();
+
+        let lo = match (0..order.len()).find(|&p| order[p] != p) {
+            Some(lo) => lo,
+            None => return,
+        };
+        let hi = (lo..order.len()).rfind(|&p| order[p] != p).unwrap();
+        // outside of lo..=hi series stay, so order[lo..=hi] is a
+        // permutation of lo..=hi.
+        let mut place = vec![0; hi - lo + 1];
+        order[lo..=hi]
+            .iter()
+            .enumerate()
+            .for_each(|(new, &old)| place[old - lo] = lo + new);
+        self.layout
+            .iter_mut()
+            .filter(|i| (lo..=hi).contains(*i))
+            .for_each(|i| *i = place[*i - lo]);
+
+        let mut old: Vec<Option<Series>> = self.y.drain(lo..=hi).map(Some).collect();
+        let sorted: Vec<Series> = order[lo..=hi]
+            .iter()
+            .map(|&i| old[i - lo].take().unwrap())
+            .collect();
+        self.y.splice(lo..lo, sorted);
+
+        // y might have been modified directly.
+        if self.index.len() != self.y.len() {
+            self.reindex();
+            return;
+        }
+        for (p, s) in self.y.iter().enumerate().take(hi + 1).skip(lo) {
+            if let Some(i) = self.index.get_mut(&s.title) {
+                *i = p;
             }
         }
     }
@@ -115,70 +351,165 @@ mod tests {
 
     #[test]
     fn append_set() {
-        let mut old = SeriesSet {
-            x: None,
-            y: vec![
+        let mut old = SeriesSet::new(
+            None,
+            None,
+            vec![
                 Series {
                     title: "a".to_owned(),
-                    values: vec![1.0, 2.0, 3.0],
+                    values: vec![1.0, 2.0, 3.0].into(),
+                    unit: None,
                 },
                 Series {
                     title: "b".to_owned(),
-                    values: vec![2.0, 3.0, 4.0],
+                    values: vec![2.0, 3.0, 4.0].into(),
+                    unit: None,
                 },
             ],
-        };
+        );
 
-        let new = SeriesSet {
-            x: None,
-            y: vec![
+        let new = SeriesSet::new(
+            None,
+            None,
+            vec![
                 Series {
                     title: "a".to_owned(),
-                    values: vec![4.0, 5.0],
+                    values: vec![4.0, 5.0].into(),
+                    unit: None,
                 },
                 Series {
                     title: "c".to_owned(),
-                    values: vec![6.0, 7.0],
+                    values: vec![6.0, 7.0].into(),
+                    unit: None,
                 },
             ],
-        };
+        );
 
         old.append_set(new);
 
         assert_eq!(old.y.len(), 3);
+        // known series stay in place, new ones are added at the end.
         assert_eq!(old.y[0].title, "a".to_owned());
-        assert_eq!(old.y[1].title, "c".to_owned());
-        assert_eq!(old.y[2].title, "b".to_owned());
+        assert_eq!(old.y[1].title, "b".to_owned());
+        assert_eq!(old.y[2].title, "c".to_owned());
         assert_eq!(old.y[0].values, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
 
+        assert_eq!(old.y[2].values.len(), 5);
+        assert!(old.y[2].values.range(0..3).all(f64::is_nan));
+        assert!(old.y[2].values.range(3..5).eq([6.0, 7.0]));
+
         assert_eq!(old.y[1].values.len(), 5);
-        assert!(old.y[1].values[0].is_nan());
-        assert!(old.y[1].values[1].is_nan());
-        assert!(old.y[1].values[2].is_nan());
-        assert_eq!(&old.y[1].values[3..5], &[6.0, 7.0]);
+        assert!(old.y[1].values.range(0..3).eq([2.0, 3.0, 4.0]));
+        assert!(old.y[1].values.range(3..5).all(f64::is_nan));
 
-        assert_eq!(old.y[2].values.len(), 5);
-        assert_eq!(&old.y[2].values[0..3], &[2.0, 3.0, 4.0]);
-        assert!(old.y[2].values[3].is_nan());
-        assert!(old.y[2].values[4].is_nan());
+        // index follows the order after sorting.
+        old.order_by(&SortingMode::ValuesDesc);
+        assert_eq!(old.y[1].title, "c".to_owned());
+        old.append_set(SeriesSet::new(
+            None,
+            None,
+            vec![Series {
+                title: "c".to_owned(),
+                values: vec![8.0].into(),
+                unit: None,
+            }],
+        ));
+        assert_eq!(old.y.len(), 3);
+        assert_eq!(old.y[1].values.sum(), 21.0);
+        assert_eq!(old.y[1].values.len(), 6);
     }
+
+    #[test]
+    fn slices_follow_sorted_series() {
+        let mut set = SeriesSet::new(
+            None,
+            None,
+            vec![Series::with_title("small"), Series::with_title("big")],
+        );
+        set.append_slice(Slice {
+            y: vec![1.0, 100.0],
+            ..Slice::default()
+        });
+        set.order_by(&SortingMode::ValuesDesc);
+        assert_eq!(set.y[0].title, "big");
+        set.append_slice(Slice {
+            y: vec![2.0],
+            ..Slice::default()
+        });
+        assert_eq!(set.y[1].values, vec![1.0, 2.0]);
+        assert!(set.y[0].values.get(1).unwrap().is_nan());
+    }
+
+    #[test]
+    fn order_by_moves_changed_series() {
+        let y = (0..20)
+            .map(|i| Series {
+                title: format!("s{}", i),
+                values: vec![i as f64].into(),
+                unit: None,
+            })
+            .collect();
+        let mut set = SeriesSet::new(None, None, y);
+        set.order_by(&SortingMode::ValuesDesc);
+        assert_eq!(set.y[0].title, "s19");
+        assert_eq!(set.y[19].title, "s0");
+
+        // slices still follow the titles of s0..s19.
+        let mut y = vec![f64::NAN; 20];
+        y[0] = 100.0;
+        y[15] = -20.0;
+        set.append_slice(Slice {
+            y,
+            ..Slice::default()
+        });
+        set.order_by(&SortingMode::ValuesDesc);
+        let titles: Vec<&str> = set.y.iter().map(|s| s.title.as_str()).collect();
+        let mut expected: Vec<String> = (1..20).rev().map(|i| format!("s{}", i)).collect();
+        expected.retain(|t| t != "s15");
+        expected.insert(0, "s0".to_owned());
+        expected.push("s15".to_owned());
+        assert_eq!(titles, expected);
+
+        // index and layout follow the moved series.
+        let mut y = vec![f64::NAN; 20];
+        y[15] = 1.0;
+        set.append_slice(Slice {
+            y,
+            ..Slice::default()
+        });
+        assert_eq!(set.y[19].values.get(2), Some(1.0));
+        set.append_set(SeriesSet::new(
+            None,
+            None,
+            vec![Series {
+                title: "s0".to_owned(),
+                values: vec![2.0].into(),
+                unit: None,
+            }],
+        ));
+        assert_eq!(set.y[0].values.get(3), Some(2.0));
+    }
+
     #[test]
     fn append_set_to_empty() {
-        let mut old = SeriesSet { x: None, y: vec![] };
+        let mut old = SeriesSet::default();
 
-        let new = SeriesSet {
-            x: None,
-            y: vec![
+        let new = SeriesSet::new(
+            None,
+            None,
+            vec![
                 Series {
                     title: "a".to_owned(),
-                    values: vec![4.0, 5.0],
+                    values: vec![4.0, 5.0].into(),
+                    unit: None,
                 },
                 Series {
                     title: "c".to_owned(),
-                    values: vec![6.0, 7.0],
+                    values: vec![6.0, 7.0].into(),
+                    unit: None,
                 },
             ],
-        };
+        );
 
         old.append_set(new);
         old.order_by(&SortingMode::ValuesDesc);
@@ -189,4 +520,88 @@ mod tests {
         assert_eq!(old.y[1].values, vec![4.0, 5.0]);
         assert_eq!(old.y[0].values, vec![6.0, 7.0]);
     }
+
+    #[test]
+    fn append_set_with_time() {
+        let mut old = SeriesSet::default();
+        old.y.push(Series {
+            title: "a".to_owned(),
+            values: vec![1.0].into(),
+            unit: None,
+        });
+
+        let new = SeriesSet::new(
+            Some((
+                "ts".to_owned(),
+                vec!["10".to_owned(), "20".to_owned()].into(),
+            )),
+            Some(vec![Some(10), Some(20)].into()),
+            vec![Series {
+                title: "a".to_owned(),
+                values: vec![2.0, 3.0].into(),
+                unit: None,
+            }],
+        );
+
+        old.append_set(new);
+        assert_eq!(
+            old.x,
+            Some((
+                "ts".to_owned(),
+                vec!["".to_owned(), "10".to_owned(), "20".to_owned()].into()
+            ))
+        );
+        assert_eq!(old.t, Some(vec![None, Some(10), Some(20)].into()));
+        assert_eq!(old.time_at(2), Some(20));
+        assert_eq!(old.index_after(1, 5), Some(2));
+        assert_eq!(old.index_after(2, -5), Some(1));
+        assert_eq!(old.index_after(2, 100), None);
+        assert_eq!(old.index_after(0, 5), None);
+    }
+
+    fn set_with_time(t: Vec<Option<Timestamp>>) -> SeriesSet {
+        let n = t.len();
+        SeriesSet::new(
+            Some((
+                "ts".to_owned(),
+                t.iter().map(|t| format!("{:?}", t)).collect(),
+            )),
+            Some(t.into()),
+            vec![Series {
+                title: "a".to_owned(),
+                values: (0..n).map(|i| i as f64).collect(),
+                unit: None,
+            }],
+        )
+    }
+
+    #[test]
+    fn evict() {
+        let mut set = set_with_time(vec![Some(10), Some(20), Some(30), Some(40)]);
+        assert_eq!(set.evict(Retention::Columns(4)), 0);
+        assert_eq!(set.evict(Retention::Columns(3)), 1);
+        assert_eq!(set.y[0].values, vec![1.0, 2.0, 3.0]);
+        assert_eq!(set.x.as_ref().unwrap().1.len(), 3);
+        assert_eq!(set.time_at(0), Some(20));
+
+        // keeps everything within 15 of the latest timestamp.
+        assert_eq!(set.evict(Retention::Duration(15)), 1);
+        assert_eq!(set.t, Some(vec![Some(30), Some(40)].into()));
+
+        let mut set = set_with_time(vec![None, Some(10), None, Some(30)]);
+        assert_eq!(set.evict(Retention::Duration(25)), 1);
+        assert_eq!(set.evict(Retention::Duration(15)), 2);
+        assert_eq!(set.y[0].values, vec![3.0]);
+    }
+
+    #[test]
+    fn retention_config() {
+        assert_eq!(Retention::new("100").unwrap(), Retention::Columns(100));
+        assert_eq!(
+            Retention::new("1h").unwrap(),
+            Retention::Duration(3_600_000_000_000)
+        );
+        assert!(Retention::new("0").is_err());
+        assert!(Retention::new("abc").is_err());
+    }
 }
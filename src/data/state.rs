@@ -1,7 +1,10 @@
 use crate::app::settings::{Settings, SortingMode};
 use crate::app::window::{Window, WindowAdjust};
+use crate::data::diagnostics::Diagnostics;
+use crate::data::rows_config::RowsConfig;
 use crate::data::scale_config::ScalesConfig;
-use crate::data::series::{SeriesSet, Slice};
+use crate::data::series::{Retention, SeriesSet, Slice};
+use crate::data::time::{TimeParser, Timestamp};
 
 #[derive(Debug)]
 pub struct State {
@@ -10,8 +13,23 @@ pub struct State {
     pub x: Window,
     pub y: Window,
     pub scales: Option<ScalesConfig>,
+    pub rows: RowsConfig,
+    pub time: Option<TimeParser>,
+    pub jump: Timestamp,
+    pub diagnostics: Diagnostics,
+    // columns dropped because main loop was not keeping up.
+    pub dropped: usize,
+    // columns merged into others for the same reason.
+    pub merged: usize,
+    // input was arriving faster than it was handled since last frame.
+    pub lagging: bool,
+    // columns which arrived while paused.
+    pub new_columns: usize,
+    keep: Option<Retention>,
     auto: bool,
+    compact: bool,
     show_cursor: bool,
+    show_diagnostics: bool,
     sort_mode: SortingMode,
 }
 
@@ -24,11 +42,25 @@ impl State {
             y: Window::default(),
             scales: settings
                 .scales
-                .as_ref()
-                .map(String::as_str)
+                .as_deref()
                 .map(|s| ScalesConfig::new(s).unwrap()),
+            rows: settings
+                .rows
+                .as_deref()
+                .map(|r| RowsConfig::new(r).unwrap())
+                .unwrap_or_default(),
+            time: settings.time.clone(),
+            jump: settings.jump,
+            diagnostics: Diagnostics::default(),
+            dropped: 0,
+            merged: 0,
+            lagging: false,
+            new_columns: 0,
+            keep: settings.keep,
             auto: true,
+            compact: settings.compact,
             show_cursor: true,
+            show_diagnostics: false,
             sort_mode: settings.sort_mode.clone(),
         }
     }
@@ -38,11 +70,37 @@ impl State {
         self.auto
     }
 
-    pub fn pause(&mut self) -> bool {
+    // Pause freezes the view only; resuming jumps to the latest data.
+    pub fn pause(&mut self, width: i64) -> bool {
         self.auto = !self.auto;
+        if self.auto {
+            self.new_columns = 0;
+            self.data.order_by(&self.sort_mode);
+            WindowAdjust::new(self.data.series_size(), width, &mut self.x).end();
+        }
         true
     }
 
+    // lines each series takes on the screen: title and chart rows.
+    // In compact layout titles are on the left of the chart.
+    pub fn heights(&self) -> Vec<i64> {
+        let title = if self.compact { 0 } else { 1 };
+        self.data
+            .y
+            .iter()
+            .map(|s| title + self.rows.rows(&s.title) as i64)
+            .collect()
+    }
+
+    pub fn toggle_compact(&mut self) -> bool {
+        self.compact = !self.compact;
+        true
+    }
+
+    pub fn is_compact(&self) -> bool {
+        self.compact
+    }
+
     pub fn hide_cursor(&mut self) -> bool {
         self.show_cursor = !self.show_cursor;
         true
@@ -52,9 +110,37 @@ impl State {
         self.show_cursor
     }
 
+    pub fn toggle_diagnostics(&mut self) -> bool {
+        self.show_diagnostics = !self.show_diagnostics;
+        // warning is shown in the pane as well.
+        self.diagnostics.warning = None;
+        true
+    }
+
+    pub fn diagnostics_shown(&self) -> bool {
+        self.show_diagnostics
+    }
+
+    // drops the data out of retention, keeping the view on the same data.
+    fn evict(&mut self) {
+        if let Some(keep) = self.keep {
+            let evicted = self.data.evict(keep);
+            self.x.on_evict(evicted as i64);
+        }
+    }
+
+    fn count_new(&mut self, before: i64) {
+        if !self.auto {
+            self.new_columns += (self.data.series_size() - before).max(0) as usize;
+        }
+    }
+
     pub fn append_slice(&mut self, slice: Slice, width: i64) {
         self.error_message = None;
+        let before = self.data.series_size();
         self.data.append_slice(slice);
+        self.count_new(before);
+        self.evict();
         let mut xm = WindowAdjust::new(self.data.series_size(), width, &mut self.x);
         xm.on_data();
         if self.auto {
@@ -64,8 +150,14 @@ impl State {
 
     pub fn extend_dataset(&mut self, d: SeriesSet, width: i64) {
         self.error_message = None;
+        let before = self.data.series_size();
         self.data.append_set(d);
-        self.data.order_by(&self.sort_mode);
+        self.count_new(before);
+        self.evict();
+        // paused view keeps the order of the series as well.
+        if self.auto {
+            self.data.order_by(&self.sort_mode);
+        }
         let mut xm = WindowAdjust::new(self.data.series_size(), width, &mut self.x);
         xm.on_data();
         if self.auto {
@@ -73,7 +165,76 @@ impl State {
         }
     }
 
+    pub fn on_ingest(&mut self, dropped: usize, merged: usize, lagging: bool) {
+        self.dropped = dropped;
+        self.merged = merged;
+        self.lagging = lagging;
+    }
+
     pub fn on_error(&mut self, e: String) {
         self.error_message = Some(e);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::ingest::Overflow;
+    use crate::app::settings::{Column, Header};
+    use crate::data::series::Series;
+    use crate::ui::glyphs::Glyphs;
+    use crate::ui::palette::Palette;
+    use crate::ui::theme::Theme;
+    use std::collections::HashMap;
+
+    fn settings() -> Settings {
+        Settings {
+            input_file: None,
+            x: Column::None,
+            header: Header::Auto,
+            only: None,
+            drop: None,
+            aliases: HashMap::new(),
+            time: None,
+            jump: 1,
+            resample: None,
+            tick: None,
+            keep: None,
+            scales: None,
+            rows: None,
+            compact: false,
+            theme: Theme::default(),
+            palette: Palette::default(),
+            glyphs: Glyphs::Blocks,
+            paired: false,
+            pause_input: false,
+            idle: None,
+            sort_mode: SortingMode::ValuesDesc,
+            fps: 30,
+            overflow: Overflow::Block,
+        }
+    }
+
+    fn slice(y: Vec<f64>) -> Slice {
+        Slice {
+            y,
+            ..Slice::default()
+        }
+    }
+
+    #[test]
+    fn slices_after_resume() {
+        let mut state = State::from_settings(&settings());
+        let header = vec![Series::with_title("small"), Series::with_title("big")];
+        state.extend_dataset(SeriesSet::new(None, None, header), 80);
+        state.append_slice(slice(vec![1.0, 100.0]), 80);
+
+        state.pause(80);
+        state.pause(80);
+        // resuming sorts the series, columns still go to their own ones.
+        assert_eq!(state.data.y[0].title, "big");
+        state.append_slice(slice(vec![2.0, 200.0]), 80);
+        assert_eq!(state.data.y[0].values, vec![100.0, 200.0]);
+        assert_eq!(state.data.y[1].values, vec![1.0, 2.0]);
+    }
+}
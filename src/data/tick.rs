@@ -0,0 +1,256 @@
+use crate::data::aggregate::{Accumulator, Reducer};
+use crate::data::fetcher::ReaderMessage;
+use crate::data::series::{Series, SeriesSet, Slice};
+use crate::data::time::{TimeParser, Timestamp};
+use crate::data::unit::Unit;
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct TickSettings {
+    pub interval: Timestamp,
+    pub reducer: Reducer,
+}
+
+/// TickAggregator collects everything the reader produced between two
+/// ticks and turns it into exactly one chart column. Series are matched
+/// by title, so the header might change within a tick; in that case
+/// the column is emitted as a new set, otherwise as a plain slice.
+pub struct TickAggregator {
+    reducer: Reducer,
+    time: TimeParser,
+    x_title: String,
+    titles: Vec<String>,
+    // position of each series in titles.
+    index: HashMap<String, usize>,
+    values: Vec<Accumulator>,
+    units: Vec<Option<Unit>>,
+    // units changed since last emitted column.
+    units_changed: bool,
+    // maps reader's current column index to the index in titles.
+    schema: Vec<usize>,
+    // new titles appeared since last emitted column.
+    changed: bool,
+    // anything was received since last emitted column.
+    received: bool,
+}
+
+impl TickAggregator {
+    pub fn new(settings: &TickSettings, time: TimeParser, x_title: String) -> TickAggregator {
+        TickAggregator {
+            reducer: settings.reducer,
+            time,
+            x_title,
+            titles: vec![],
+            index: HashMap::new(),
+            values: vec![],
+            units: vec![],
+            units_changed: false,
+            schema: vec![],
+            changed: false,
+            received: false,
+        }
+    }
+
+    fn index_of(&mut self, title: &str) -> usize {
+        match self.index.get(title) {
+            Some(&i) => i,
+            None => {
+                self.index.insert(title.to_owned(), self.titles.len());
+                self.titles.push(title.to_owned());
+                self.values.push(Accumulator::default());
+                self.units.push(None);
+                self.changed = true;
+                self.titles.len() - 1
+            }
+        }
+    }
+
+    fn add(&mut self, y: impl Iterator<Item = f64>) {
+        self.received = true;
+        let values = &mut self.values;
+        self.schema
+            .iter()
+            .zip(y)
+            .for_each(|(&i, v)| values[i].add(v));
+    }
+
+    fn set_units(&mut self, units: impl Iterator<Item = Option<Unit>>) {
+        let schema = &self.schema;
+        let own = &mut self.units;
+        let mut changed = false;
+        schema.iter().zip(units).for_each(|(&i, u)| {
+            if u.is_some() && own[i] != u {
+                own[i] = u;
+                changed = true;
+            }
+        });
+        self.units_changed |= changed;
+    }
+
+    pub fn on_message(&mut self, message: ReaderMessage) {
+        match message {
+            ReaderMessage::Append(slice) => {
+                if let Some(units) = slice.units {
+                    self.set_units(units.into_iter());
+                }
+                self.add(slice.y.into_iter());
+            }
+            ReaderMessage::Extend(set) => {
+                self.schema = set.y.iter().map(|s| self.index_of(&s.title)).collect();
+                self.set_units(set.y.iter().map(|s| s.unit.clone()));
+                set.columns().for_each(|c| self.add(c.into_iter()));
+            }
+            ReaderMessage::Report(_) | ReaderMessage::Eof => {}
+        }
+    }
+
+    pub fn has_data(&self) -> bool {
+        self.received
+    }
+
+    // Builds the column for the interval which ends at 'now'
+    // and starts accumulating next one.
+    pub fn tick(&mut self, now: Timestamp) -> ReaderMessage {
+        let reducer = self.reducer;
+        let y: Vec<f64> = self.values.iter().map(|a| a.value(reducer)).collect();
+        self.values
+            .iter_mut()
+            .for_each(|a| *a = Accumulator::default());
+        self.received = false;
+
+        let x = self.time.format_full(now);
+        let units_changed = std::mem::replace(&mut self.units_changed, false);
+        if self.changed {
+            self.changed = false;
+            ReaderMessage::Extend(SeriesSet::new(
+                Some((self.x_title.clone(), vec![x].into())),
+                Some(vec![Some(now)].into()),
+                self.titles
+                    .iter()
+                    .zip(self.units.iter())
+                    .zip(y)
+                    .map(|((title, unit), v)| Series {
+                        title: title.clone(),
+                        values: vec![v].into(),
+                        unit: unit.clone(),
+                    })
+                    .collect(),
+            ))
+        } else {
+            ReaderMessage::Append(Slice {
+                x: Some(x),
+                t: Some(Some(now)),
+                y,
+                units: if units_changed {
+                    Some(self.units.clone())
+                } else {
+                    None
+                },
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::settings::SortingMode;
+    use crate::data::time::{TimeFormat, Zone};
+
+    fn set(titles: &[&str], values: &[f64]) -> ReaderMessage {
+        ReaderMessage::Extend(SeriesSet::new(
+            None,
+            None,
+            titles
+                .iter()
+                .zip(values)
+                .map(|(t, v)| Series {
+                    title: t.to_string(),
+                    values: vec![*v].into(),
+                    unit: None,
+                })
+                .collect(),
+        ))
+    }
+
+    fn slice(y: Vec<f64>) -> ReaderMessage {
+        ReaderMessage::Append(Slice {
+            x: None,
+            t: None,
+            y,
+            units: None,
+        })
+    }
+
+    #[test]
+    fn aggregates_per_tick() {
+        let settings = TickSettings {
+            interval: 10,
+            reducer: Reducer::Sum,
+        };
+        let time = TimeParser::new(TimeFormat::EpochNanos, Zone::Utc);
+        let mut agg = TickAggregator::new(&settings, time, "time".to_owned());
+        let mut res = SeriesSet::default();
+
+        agg.on_message(set(&["a", "b"], &[1.0, 2.0]));
+        agg.on_message(slice(vec![3.0, 4.0]));
+        assert!(agg.has_data());
+        match agg.tick(10) {
+            ReaderMessage::Extend(s) => res.append_set(s),
+            _ => panic!("new titles must produce a set"),
+        }
+        assert!(!agg.has_data());
+
+        // no input during this tick
+        match agg.tick(20) {
+            ReaderMessage::Append(s) => res.append_slice(s),
+            _ => panic!("known titles must produce a slice"),
+        }
+
+        // header changes within the tick
+        agg.on_message(set(&["c", "a"], &[5.0, 6.0]));
+        agg.on_message(slice(vec![7.0, 8.0]));
+        match agg.tick(30) {
+            ReaderMessage::Extend(s) => res.append_set(s),
+            _ => panic!("new titles must produce a set"),
+        }
+
+        assert_eq!(res.t, Some(vec![Some(10), Some(20), Some(30)].into()));
+        let a = res.y.iter().find(|s| s.title == "a").unwrap();
+        assert_eq!(a.values.get(0), Some(4.0));
+        assert!(a.values.get(1).unwrap().is_nan());
+        assert_eq!(a.values.get(2), Some(14.0));
+        let c = res.y.iter().find(|s| s.title == "c").unwrap();
+        assert!(c.values.get(0).unwrap().is_nan());
+        assert_eq!(c.values.get(2), Some(12.0));
+    }
+
+    #[test]
+    fn ticks_follow_sorted_series() {
+        let settings = TickSettings {
+            interval: 10,
+            reducer: Reducer::Last,
+        };
+        let time = TimeParser::new(TimeFormat::EpochNanos, Zone::Utc);
+        let mut agg = TickAggregator::new(&settings, time, "time".to_owned());
+        let mut res = SeriesSet::default();
+
+        // first values are in ascending order, so sorting swaps the series.
+        agg.on_message(set(&["a", "b"], &[1.0, 2.0]));
+        match agg.tick(10) {
+            ReaderMessage::Extend(s) => res.append_set(s),
+            _ => panic!("new titles must produce a set"),
+        }
+        res.order_by(&SortingMode::ValuesDesc);
+        assert_eq!(res.y[0].title, "b");
+
+        agg.on_message(slice(vec![3.0, 4.0]));
+        match agg.tick(20) {
+            ReaderMessage::Append(s) => res.append_slice(s),
+            _ => panic!("known titles must produce a slice"),
+        }
+        assert_eq!(res.y[0].values, vec![2.0, 4.0]);
+        assert_eq!(res.y[1].values, vec![1.0, 3.0]);
+    }
+}
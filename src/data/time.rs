@@ -0,0 +1,255 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// nanoseconds since unix epoch (or duration in nanoseconds)
+pub type Timestamp = i64;
+
+const NANOS_IN_SECOND: i64 = 1_000_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeFormat {
+    EpochSeconds,
+    EpochMillis,
+    EpochMicros,
+    EpochNanos,
+    Rfc3339,
+    // strftime-like pattern, e.g. '%Y-%m-%d %H:%M:%S'
+    Pattern(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    Local,
+    Utc,
+}
+
+// TimeParser converts x values to timestamps and timestamps back to
+// human-readable labels. Patterns without an offset are interpreted
+// in the configured zone; labels are always shown in that zone.
+#[derive(Debug, Clone)]
+pub struct TimeParser {
+    format: TimeFormat,
+    zone: Zone,
+}
+
+impl TimeFormat {
+    pub fn new(conf: &str) -> Result<TimeFormat, TimeError> {
+        match conf {
+            "epoch" | "s" => Ok(TimeFormat::EpochSeconds),
+            "ms" => Ok(TimeFormat::EpochMillis),
+            "us" => Ok(TimeFormat::EpochMicros),
+            "ns" => Ok(TimeFormat::EpochNanos),
+            "rfc3339" => Ok(TimeFormat::Rfc3339),
+            p if p.contains('%') => Ok(TimeFormat::Pattern(p.to_owned())),
+            _ => Err(TimeError::BadFormat(conf.to_owned())),
+        }
+    }
+}
+
+impl TimeParser {
+    pub fn new(format: TimeFormat, zone: Zone) -> TimeParser {
+        TimeParser { format, zone }
+    }
+
+    pub fn parse(&self, v: &str) -> Option<Timestamp> {
+        let v = v.trim();
+        match &self.format {
+            TimeFormat::EpochSeconds => epoch(v, NANOS_IN_SECOND),
+            TimeFormat::EpochMillis => epoch(v, 1_000_000),
+            TimeFormat::EpochMicros => epoch(v, 1_000),
+            TimeFormat::EpochNanos => v.parse::<i64>().ok(),
+            TimeFormat::Rfc3339 => DateTime::parse_from_rfc3339(v)
+                .ok()
+                .and_then(|t| t.timestamp_nanos_opt()),
+            TimeFormat::Pattern(p) => DateTime::parse_from_str(v, p)
+                .ok()
+                .and_then(|t| t.timestamp_nanos_opt())
+                .or_else(|| {
+                    let naive = NaiveDateTime::parse_from_str(v, p).ok()?;
+                    match self.zone {
+                        Zone::Utc => Utc.from_utc_datetime(&naive).timestamp_nanos_opt(),
+                        Zone::Local => Local
+                            .from_local_datetime(&naive)
+                            .earliest()?
+                            .timestamp_nanos_opt(),
+                    }
+                }),
+        }
+    }
+
+    pub fn format(&self, t: Timestamp, pattern: &str) -> String {
+        let utc = Utc.timestamp_nanos(t);
+        match self.zone {
+            Zone::Utc => utc.format(pattern).to_string(),
+            Zone::Local => utc.with_timezone(&Local).format(pattern).to_string(),
+        }
+    }
+
+    // full timestamp, used for cursor labels.
+    pub fn format_full(&self, t: Timestamp) -> String {
+        self.format(t, "%Y-%m-%d %H:%M:%S%.3f")
+    }
+
+    // label for axis, precise enough for visible time span.
+    pub fn format_for_span(&self, t: Timestamp, span: Timestamp) -> String {
+        self.format(t, span_pattern(span))
+    }
+}
+
+// current wall-clock time
+pub fn now() -> Timestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as Timestamp)
+        .unwrap_or(0)
+}
+
+fn epoch(v: &str, nanos_per_unit: i64) -> Option<Timestamp> {
+    if let Ok(i) = v.parse::<i64>() {
+        return i.checked_mul(nanos_per_unit);
+    }
+    v.parse::<f64>()
+        .ok()
+        .filter(|f| f.is_finite())
+        .map(|f| (f * nanos_per_unit as f64).round() as Timestamp)
+}
+
+fn span_pattern(span: Timestamp) -> &'static str {
+    let span = span.abs();
+    if span < 60 * NANOS_IN_SECOND {
+        "%H:%M:%S%.3f"
+    } else if span < 24 * 3600 * NANOS_IN_SECOND {
+        "%H:%M:%S"
+    } else if span < 365 * 24 * 3600 * NANOS_IN_SECOND {
+        "%m-%d %H:%M"
+    } else {
+        "%Y-%m-%d"
+    }
+}
+
+// Parses durations like '500ms', '10s', '5m', '24h', '7d'.
+// Number without suffix is treated as seconds.
+pub fn parse_duration(s: &str) -> Result<Timestamp, TimeError> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let multiplier = match unit {
+        "ns" => 1,
+        "us" => 1_000,
+        "ms" => 1_000_000,
+        "" | "s" => NANOS_IN_SECOND,
+        "m" => 60 * NANOS_IN_SECOND,
+        "h" => 3600 * NANOS_IN_SECOND,
+        "d" => 24 * 3600 * NANOS_IN_SECOND,
+        _ => return Err(TimeError::BadDuration(s.to_owned())),
+    };
+    let value = value
+        .parse::<f64>()
+        .map_err(|_| TimeError::BadDuration(s.to_owned()))?;
+    if !value.is_finite() || value <= 0.0 {
+        return Err(TimeError::BadDuration(s.to_owned()));
+    }
+    Ok((value * multiplier as f64).round() as Timestamp)
+}
+
+// Same as parse_duration, but zero is allowed, e.g. for no delay.
+pub fn parse_delay(s: &str) -> Result<Timestamp, TimeError> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    match value.parse::<f64>() {
+        Ok(0.0) => parse_duration(&format!("1{}", unit))
+            .map(|_| 0)
+            .map_err(|_| TimeError::BadDuration(s.to_owned())),
+        _ => parse_duration(s),
+    }
+}
+
+#[derive(Debug)]
+pub enum TimeError {
+    BadFormat(String),
+    BadDuration(String),
+}
+
+impl std::fmt::Display for TimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            TimeError::BadFormat(ref s) => write!(f, "Bad time format: {}", s),
+            TimeError::BadDuration(ref s) => write!(f, "Bad duration: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(format: &str) -> TimeParser {
+        TimeParser::new(TimeFormat::new(format).unwrap(), Zone::Utc)
+    }
+
+    #[test]
+    fn parse_epoch() {
+        assert_eq!(
+            utc("s").parse("1500000000"),
+            Some(1_500_000_000_000_000_000)
+        );
+        assert_eq!(utc("s").parse("1.5"), Some(1_500_000_000));
+        assert_eq!(utc("ms").parse("1500"), Some(1_500_000_000));
+        assert_eq!(utc("us").parse("1500"), Some(1_500_000));
+        assert_eq!(utc("ns").parse("1500"), Some(1500));
+        assert_eq!(utc("s").parse("x"), None);
+    }
+
+    #[test]
+    fn parse_dates() {
+        assert_eq!(
+            utc("rfc3339").parse("1970-01-01T00:00:01+00:00"),
+            Some(NANOS_IN_SECOND)
+        );
+        assert_eq!(
+            utc("rfc3339").parse("1970-01-01T01:00:01+01:00"),
+            Some(NANOS_IN_SECOND)
+        );
+        assert_eq!(
+            utc("%Y-%m-%d %H:%M:%S").parse("1970-01-01 00:01:00"),
+            Some(60 * NANOS_IN_SECOND)
+        );
+        assert_eq!(
+            utc("%Y-%m-%d %H:%M:%S %z").parse("1970-01-01 02:01:00 +0200"),
+            Some(60 * NANOS_IN_SECOND)
+        );
+        assert_eq!(utc("%Y-%m-%d %H:%M:%S").parse("yesterday"), None);
+        assert!(TimeFormat::new("unknown").is_err());
+    }
+
+    #[test]
+    fn format_labels() {
+        let p = utc("s");
+        assert_eq!(p.format_full(1_500_000_000), "1970-01-01 00:00:01.500");
+        assert_eq!(
+            p.format_for_span(3600 * NANOS_IN_SECOND, 3600 * NANOS_IN_SECOND),
+            "01:00:00"
+        );
+        assert_eq!(
+            p.format_for_span(0, 1000 * 24 * 3600 * NANOS_IN_SECOND),
+            "1970-01-01"
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("10").unwrap(), 10 * NANOS_IN_SECOND);
+        assert_eq!(parse_duration("250ms").unwrap(), 250_000_000);
+        assert_eq!(parse_duration("1.5m").unwrap(), 90 * NANOS_IN_SECOND);
+        assert_eq!(parse_duration("24h").unwrap(), 24 * 3600 * NANOS_IN_SECOND);
+        assert!(parse_duration("1w").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("ms").is_err());
+        assert!(parse_duration("0").is_err());
+        assert_eq!(parse_delay("0").unwrap(), 0);
+        assert_eq!(parse_delay("0s").unwrap(), 0);
+        assert_eq!(parse_delay("2s").unwrap(), 2 * NANOS_IN_SECOND);
+        assert!(parse_delay("0w").is_err());
+    }
+}
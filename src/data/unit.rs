@@ -0,0 +1,338 @@
+// Units which can be detected from the data.
+// Values with a unit suffix are stored in base units: seconds, bytes, percents.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unit {
+    Percent,
+    Seconds,
+    Bytes,
+    BytesPerSecond,
+    PerSecond,
+    // multiple of the unit declared for the series, e.g. 'latency_ms'.
+    // Values are kept as they are, and only formatted in base units.
+    Scaled(f64, Option<Box<Unit>>),
+}
+
+impl Unit {
+    // unit of the values declared with the multiplier to base units.
+    pub fn declared(multiplier: f64, unit: Option<Unit>) -> Option<Unit> {
+        if multiplier == 1.0 {
+            unit
+        } else {
+            Some(Unit::Scaled(multiplier, unit.map(Box::new)))
+        }
+    }
+}
+
+// multiplier and unit for the suffix after the number.
+fn suffix(s: &str) -> Option<(f64, Option<Unit>)> {
+    let (m, u) = match s {
+        "" => (1.0, None),
+        "%" => (1.0, Some(Unit::Percent)),
+
+        // durations
+        "ns" => (1.0e-9, Some(Unit::Seconds)),
+        "us" | "µs" => (1.0e-6, Some(Unit::Seconds)),
+        "ms" => (1.0e-3, Some(Unit::Seconds)),
+        "s" | "sec" => (1.0, Some(Unit::Seconds)),
+        "min" => (60.0, Some(Unit::Seconds)),
+        "h" => (3600.0, Some(Unit::Seconds)),
+        "d" => (86400.0, Some(Unit::Seconds)),
+
+        // bytes
+        "B" => (1.0, Some(Unit::Bytes)),
+        "KB" | "kB" => (1.0e3, Some(Unit::Bytes)),
+        "MB" => (1.0e6, Some(Unit::Bytes)),
+        "GB" => (1.0e9, Some(Unit::Bytes)),
+        "TB" => (1.0e12, Some(Unit::Bytes)),
+        "KiB" => (1024.0, Some(Unit::Bytes)),
+        "MiB" => (1024.0 * 1024.0, Some(Unit::Bytes)),
+        "GiB" => (1024.0 * 1024.0 * 1024.0, Some(Unit::Bytes)),
+        "TiB" => (1024.0 * 1024.0 * 1024.0 * 1024.0, Some(Unit::Bytes)),
+
+        // plain multipliers
+        "n" => (1.0e-9, None),
+        "u" | "µ" => (1.0e-6, None),
+        "m" => (1.0e-3, None),
+        "k" | "K" => (1.0e3, None),
+        "M" => (1.0e6, None),
+        "G" => (1.0e9, None),
+        "T" => (1.0e12, None),
+        "Ki" => (1024.0, None),
+        "Mi" => (1024.0 * 1024.0, None),
+        "Gi" => (1024.0 * 1024.0 * 1024.0, None),
+        "Ti" => (1024.0 * 1024.0 * 1024.0 * 1024.0, None),
+        _ => return None,
+    };
+    Some((m, u))
+}
+
+// '1,234,567.8' -> '1234567.8'; None if commas are not thousands separators.
+fn strip_thousands(s: &str) -> Option<String> {
+    let int_part = s.split('.').next()?;
+    let int_part = int_part.trim_start_matches(['-', '+']);
+    let mut groups = int_part.split(',');
+    let first = groups.next()?;
+    if first.is_empty() || first.len() > 3 || !first.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if !groups.all(|g| g.len() == 3 && g.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    Some(s.replace(',', ""))
+}
+
+// length of the numeric prefix: [+-]digits[.digits][e[+-]digits]
+fn number_len(s: &str) -> usize {
+    let b = s.as_bytes();
+    let digits = |mut i: usize| {
+        while i < b.len() && b[i].is_ascii_digit() {
+            i += 1;
+        }
+        i
+    };
+    let mut i = 0;
+    if i < b.len() && (b[i] == b'-' || b[i] == b'+') {
+        i += 1;
+    }
+    i = digits(i);
+    if i < b.len() && b[i] == b'.' {
+        i = digits(i + 1);
+    }
+    if i < b.len() && (b[i] == b'e' || b[i] == b'E') {
+        let mut j = i + 1;
+        if j < b.len() && (b[j] == b'-' || b[j] == b'+') {
+            j += 1;
+        }
+        let k = digits(j);
+        // 'e' is part of the number only if followed by exponent digits
+        if k > j {
+            i = k;
+        }
+    }
+    i
+}
+
+/// Parses a data cell, which might have a unit or multiplier suffix:
+/// 12K, 3.4GiB, 45%, 250ms, 1.2e3/s, 1,234.
+/// Returns the value in base units and the unit, if detected.
+pub fn parse_value(s: &str) -> Option<(f64, Option<Unit>)> {
+    let s = s.trim();
+    if let Ok(v) = s.parse::<f64>() {
+        return Some((v, None));
+    }
+    let stripped;
+    let s = if s.contains(',') {
+        let end = s
+            .find(|c: char| !(c.is_ascii_digit() || c == ',' || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(s.len());
+        let (number, rest) = s.split_at(end);
+        stripped = strip_thousands(number)? + rest;
+        stripped.as_str()
+    } else {
+        s
+    };
+
+    let (number, rest) = s.split_at(number_len(s));
+    let value = number.parse::<f64>().ok()?;
+    let (multiplier, unit) = parse_suffix(rest)?;
+    Some((value * multiplier, unit))
+}
+
+// suffix, optionally followed by '/s' for rates.
+fn parse_suffix(s: &str) -> Option<(f64, Option<Unit>)> {
+    let s = s.trim();
+    let (s, rate) = match s.strip_suffix("/s") {
+        Some(r) => (r, true),
+        None => (s, false),
+    };
+    let (multiplier, unit) = suffix(s)?;
+    let unit = match (unit, rate) {
+        (u, false) => u,
+        (None, true) => Some(Unit::PerSecond),
+        (Some(Unit::Bytes), true) => Some(Unit::BytesPerSecond),
+        (Some(_), true) => return None,
+    };
+    Some((multiplier, unit))
+}
+
+/// Parses a cell of the header units row: 'ms', 'KiB', '%', '[B/s]', 'bytes'.
+/// Returns the multiplier to base units and the unit, if any.
+pub fn parse_unit(s: &str) -> Option<(f64, Option<Unit>)> {
+    let s = s.trim();
+    let s = s
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .or_else(|| s.strip_prefix('(').and_then(|s| s.strip_suffix(')')))
+        .unwrap_or(s);
+    match s.to_lowercase().as_str() {
+        "bytes" => Some((1.0, Some(Unit::Bytes))),
+        "seconds" => Some((1.0, Some(Unit::Seconds))),
+        "pct" | "percent" => Some((1.0, Some(Unit::Percent))),
+        _ => parse_suffix(s),
+    }
+}
+
+/// Detects the unit from the series title suffix: 'rss_bytes', 'latency_ms', 'cpu_pct'.
+pub fn unit_from_title(title: &str) -> Option<(f64, Unit)> {
+    let (_, s) = title.rsplit_once('_')?;
+    let (m, u) = match s.to_lowercase().as_str() {
+        "bytes" => (1.0, Unit::Bytes),
+        "seconds" | "sec" => (1.0, Unit::Seconds),
+        "ms" => (1.0e-3, Unit::Seconds),
+        "us" => (1.0e-6, Unit::Seconds),
+        "ns" => (1.0e-9, Unit::Seconds),
+        "pct" | "percent" => (1.0, Unit::Percent),
+        _ => return None,
+    };
+    Some((m, u))
+}
+
+const SI: [(f64, &str); 5] = [
+    (1.0e12, "T"),
+    (1.0e9, "G"),
+    (1.0e6, "M"),
+    (1.0e3, "k"),
+    (1.0, ""),
+];
+
+const BINARY: [(f64, &str); 5] = [
+    (1024.0 * 1024.0 * 1024.0 * 1024.0, "Ti"),
+    (1024.0 * 1024.0 * 1024.0, "Gi"),
+    (1024.0 * 1024.0, "Mi"),
+    (1024.0, "Ki"),
+    (1.0, ""),
+];
+
+const DURATIONS: [(f64, &str); 7] = [
+    (86400.0, "d"),
+    (3600.0, "h"),
+    (60.0, "min"),
+    (1.0, "s"),
+    (1.0e-3, "ms"),
+    (1.0e-6, "us"),
+    (1.0e-9, "ns"),
+];
+
+// at most 3 decimal digits, without trailing zeroes.
+fn short(v: f64) -> String {
+    let s = format!("{:.3}", v);
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.').to_owned()
+    } else {
+        s
+    }
+}
+
+// picks the largest multiplier not exceeding the value,
+// values beyond a thousand of the largest one use exponent.
+fn with_prefix(v: f64, prefixes: &[(f64, &str)], unit: &str) -> String {
+    let a = v.abs();
+    if a >= prefixes[0].0 * 1.0e3 {
+        return format!("{:.3e}{}", v, unit);
+    }
+    match prefixes.iter().find(|(m, _)| a >= *m) {
+        Some((m, p)) => format!("{}{}{}", short(v / m), p, unit),
+        None if a == 0.0 => format!("0{}", unit),
+        None if a < 1.0e-3 => format!("{:.3e}{}", v, unit),
+        None => format!("{}{}", short(v), unit),
+    }
+}
+
+/// Formats the value for display, using SI or binary prefixes for the unit:
+/// 1073741824 bytes -> '1GiB', 0.00025 seconds -> '250us'.
+pub fn format_value(v: f64, unit: Option<&Unit>) -> String {
+    if !v.is_finite() {
+        return format!("{}", v);
+    }
+    match unit {
+        None => with_prefix(v, &SI, ""),
+        Some(Unit::Percent) => format!("{}%", short(v)),
+        Some(Unit::Seconds) => with_prefix(v, &DURATIONS, ""),
+        Some(Unit::Bytes) => with_prefix(v, &BINARY, "B"),
+        Some(Unit::BytesPerSecond) => with_prefix(v, &BINARY, "B/s"),
+        Some(Unit::PerSecond) => with_prefix(v, &SI, "/s"),
+        Some(Unit::Scaled(m, u)) => format_value(v * m, u.as_deref()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn check(s: &str, expected: f64, unit: Option<Unit>) {
+        let (v, u) = parse_value(s).unwrap();
+        assert_approx_eq!(v, expected);
+        assert_eq!(u, unit);
+    }
+
+    #[test]
+    fn parse_values() {
+        check("12", 12.0, None);
+        check(" -1.5e3 ", -1500.0, None);
+        check("12K", 12000.0, None);
+        check("2Ki", 2048.0, None);
+        check("3m", 0.003, None);
+        check("3.5GiB", 3.5 * 1024.0 * 1024.0 * 1024.0, Some(Unit::Bytes));
+        check("10 MB", 1.0e7, Some(Unit::Bytes));
+        check("45%", 45.0, Some(Unit::Percent));
+        check("250ms", 0.25, Some(Unit::Seconds));
+        check("3us", 3.0e-6, Some(Unit::Seconds));
+        check("1.2e3/s", 1200.0, Some(Unit::PerSecond));
+        check("1KiB/s", 1024.0, Some(Unit::BytesPerSecond));
+        check("1,234", 1234.0, None);
+        check("1,234,567.5ms", 1234.5675, Some(Unit::Seconds));
+    }
+
+    #[test]
+    fn parse_failures() {
+        assert_eq!(parse_value(""), None);
+        assert_eq!(parse_value("abc"), None);
+        assert_eq!(parse_value("12OLOLOL"), None);
+        assert_eq!(parse_value("1,23"), None);
+        assert_eq!(parse_value("5%/s"), None);
+    }
+
+    #[test]
+    fn units_and_titles() {
+        assert_eq!(parse_unit("ms"), Some((1.0e-3, Some(Unit::Seconds))));
+        assert_eq!(parse_unit("[B/s]"), Some((1.0, Some(Unit::BytesPerSecond))));
+        assert_eq!(parse_unit("Percent"), Some((1.0, Some(Unit::Percent))));
+        assert_eq!(parse_unit(""), Some((1.0, None)));
+        assert_eq!(parse_unit("12"), None);
+        assert_eq!(unit_from_title("rss_bytes"), Some((1.0, Unit::Bytes)));
+        assert_eq!(unit_from_title("p99_ms"), Some((1.0e-3, Unit::Seconds)));
+        assert_eq!(unit_from_title("cpu_pct"), Some((1.0, Unit::Percent)));
+        assert_eq!(unit_from_title("requests"), None);
+        assert_eq!(unit_from_title("read_s"), None);
+    }
+
+    #[test]
+    fn format_values() {
+        assert_eq!(format_value(1073741824.0, Some(&Unit::Bytes)), "1GiB");
+        assert_eq!(
+            format_value(1536.0, Some(&Unit::BytesPerSecond)),
+            "1.5KiB/s"
+        );
+        assert_eq!(format_value(0.00025, Some(&Unit::Seconds)), "250us");
+        assert_eq!(format_value(90.0, Some(&Unit::Seconds)), "1.5min");
+        assert_eq!(format_value(45.25, Some(&Unit::Percent)), "45.25%");
+        assert_eq!(format_value(12000.0, Some(&Unit::PerSecond)), "12k/s");
+        assert_eq!(format_value(-2500000.0, None), "-2.5M");
+        assert_eq!(format_value(0.5, None), "0.5");
+        assert_eq!(format_value(0.0, Some(&Unit::Bytes)), "0B");
+        assert_eq!(format_value(0.00002, None), "2.000e-5");
+        assert_eq!(format_value(999.0e12, None), "999T");
+        assert_eq!(format_value(-1.0e300, None), "-1.000e300");
+        assert_eq!(format_value(2.0e15, Some(&Unit::Bytes)), "2.000e15B");
+        assert_eq!(format_value(f64::NAN, None), "NaN");
+
+        let ms = Unit::declared(1.0e-3, Some(Unit::Seconds));
+        assert_eq!(format_value(250.0, ms.as_ref()), "250ms");
+        assert_eq!(
+            format_value(2.0, Unit::declared(1.0e3, None).as_ref()),
+            "2k"
+        );
+        assert_eq!(Unit::declared(1.0, Some(Unit::Bytes)), Some(Unit::Bytes));
+    }
+}
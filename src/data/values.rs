@@ -0,0 +1,414 @@
+use std::collections::VecDeque;
+use std::iter::{self, FromIterator};
+use std::ops::Range;
+
+use crate::data::gorilla::{Decoder, Encoder};
+
+// number of values in a sealed block.
+const BLOCK: usize = 256;
+
+// Summary of the finite values in a block.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Summary {
+    pub min: f64,
+    pub max: f64,
+    pub sum: f64,
+    pub count: usize,
+}
+
+impl Summary {
+    fn add(&mut self, v: f64) {
+        if !v.is_finite() {
+            return;
+        }
+        if self.count == 0 {
+            self.min = v;
+            self.max = v;
+        } else {
+            self.min = self.min.min(v);
+            self.max = self.max.max(v);
+        }
+        self.sum += v;
+        self.count += 1;
+    }
+
+    fn of(values: &[f64]) -> Summary {
+        let mut summary = Summary::default();
+        values.iter().for_each(|&v| summary.add(v));
+        summary
+    }
+
+    pub fn merge(mut self, other: &Summary) -> Summary {
+        if other.count > 0 {
+            if self.count == 0 {
+                return *other;
+            }
+            self.min = self.min.min(other.min);
+            self.max = self.max.max(other.max);
+            self.sum += other.sum;
+            self.count += other.count;
+        }
+        self
+    }
+
+    pub fn bounds(&self) -> Option<(f64, f64)> {
+        if self.count > 0 {
+            Some((self.min, self.max))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Block {
+    // run of NaN values, adjacent runs are merged.
+    Nan(usize),
+    // values compressed with XOR encoding.
+    Packed {
+        len: usize,
+        words: Vec<u64>,
+        summary: Summary,
+    },
+}
+
+impl Block {
+    fn pack(values: &[f64]) -> Block {
+        if values.iter().all(|v| v.is_nan()) {
+            return Block::Nan(values.len());
+        }
+        let mut encoder = Encoder::default();
+        values.iter().for_each(|&v| encoder.push(v));
+        Block::Packed {
+            len: values.len(),
+            words: encoder.finish(),
+            summary: Summary::of(values),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Block::Nan(n) => *n,
+            Block::Packed { len, .. } => *len,
+        }
+    }
+
+    fn summary(&self) -> Summary {
+        match self {
+            Block::Nan(_) => Summary::default(),
+            Block::Packed { summary, .. } => *summary,
+        }
+    }
+
+    // values of the block, starting at 'from'.
+    fn values(&self, from: usize) -> impl Iterator<Item = f64> + '_ {
+        let (nans, words, len) = match self {
+            Block::Nan(n) => (n.saturating_sub(from), &[][..], 0),
+            Block::Packed { len, words, .. } => (0, &words[..], *len),
+        };
+        iter::repeat_n(f64::NAN, nans).chain(Decoder::new(words, len).skip(from))
+    }
+}
+
+/// Values is the storage for a series. Values are appended to
+/// the uncompressed tail, which is sealed into a compressed block
+/// once full. Each block keeps the summary of its values, so that
+/// min/max don't need to decompress anything. Old values can be
+/// evicted from the front.
+#[derive(Debug, Clone, Default)]
+pub struct Values {
+    blocks: VecDeque<Block>,
+    // position of the first value of each block, counted from the
+    // first value ever appended, so that eviction doesn't shift it.
+    starts: VecDeque<usize>,
+    tail: Vec<f64>,
+    tail_start: usize,
+    // summary of all the values kept, maintained on append and eviction.
+    stats: Summary,
+}
+
+impl Values {
+    pub fn new() -> Values {
+        Values::default()
+    }
+
+    // position of the first value kept.
+    fn first(&self) -> usize {
+        self.starts.front().cloned().unwrap_or(self.tail_start)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tail_start + self.tail.len() - self.first()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn push_back(&mut self, v: f64) {
+        self.stats.add(v);
+        self.tail.push(v);
+        if self.tail.len() >= BLOCK {
+            self.seal();
+        }
+    }
+
+    fn seal(&mut self) {
+        let block = Block::pack(&self.tail);
+        match (self.blocks.back_mut(), block) {
+            (Some(Block::Nan(n)), Block::Nan(m)) => *n += m,
+            (_, block) => {
+                self.starts.push_back(self.tail_start);
+                self.blocks.push_back(block);
+            }
+        }
+        self.tail_start += self.tail.len();
+        self.tail.clear();
+    }
+
+    // index of the block containing the position.
+    fn locate(&self, pos: usize) -> usize {
+        self.starts.partition_point(|&s| s <= pos) - 1
+    }
+
+    pub fn get(&self, index: usize) -> Option<f64> {
+        if index >= self.len() {
+            return None;
+        }
+        let pos = self.first() + index;
+        if pos >= self.tail_start {
+            return self.tail.get(pos - self.tail_start).cloned();
+        }
+        let b = self.locate(pos);
+        self.blocks[b].values(pos - self.starts[b]).next()
+    }
+
+    // Values in the range; only blocks overlapping the range
+    // are decompressed.
+    pub fn range(&self, range: Range<usize>) -> impl Iterator<Item = f64> + '_ {
+        let end = range.end.min(self.len());
+        let start = range.start.min(end);
+        let pos = self.first() + start;
+        let (b, from) = if pos < self.tail_start {
+            let b = self.locate(pos);
+            (b, pos - self.starts[b])
+        } else {
+            (self.blocks.len(), 0)
+        };
+        let tail_from = pos.saturating_sub(self.tail_start);
+        self.blocks
+            .range(b..)
+            .enumerate()
+            .flat_map(move |(i, block)| block.values(if i == 0 { from } else { 0 }))
+            .chain(self.tail[tail_from..].iter().cloned())
+            .take(end - start)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = f64> + '_ {
+        self.range(0..self.len())
+    }
+
+    // moves all values from other to the end of self.
+    pub fn append(&mut self, other: &mut Values) {
+        let other = std::mem::take(other);
+        if self.is_empty() {
+            *self = other;
+        } else {
+            self.extend(other.iter());
+        }
+    }
+
+    // Removes n oldest values. Block which is evicted partially is
+    // compressed again, so that its summary stays correct.
+    pub fn evict(&mut self, n: usize) {
+        let to = self.first() + n.min(self.len());
+        let mut removed = Summary::default();
+        while let Some(&start) = self.starts.front() {
+            let len = self.blocks[0].len();
+            if start + len <= to {
+                self.starts.pop_front();
+                if let Some(block) = self.blocks.pop_front() {
+                    removed = removed.merge(&block.summary());
+                }
+                continue;
+            }
+            if start < to {
+                let block = match &self.blocks[0] {
+                    Block::Nan(n) => Block::Nan(n - (to - start)),
+                    block => {
+                        let values: Vec<f64> = block.values(0).collect();
+                        removed = removed.merge(&Summary::of(&values[..to - start]));
+                        Block::pack(&values[to - start..])
+                    }
+                };
+                self.blocks[0] = block;
+                self.starts[0] = to;
+            }
+            break;
+        }
+        if to > self.tail_start {
+            let drained: Vec<f64> = self.tail.drain(..to - self.tail_start).collect();
+            removed = removed.merge(&Summary::of(&drained));
+            self.tail_start = to;
+        }
+        self.on_evict(&removed);
+    }
+
+    // Sum and count are just updated; min and max are found again
+    // from block summaries only if one of them was evicted.
+    fn on_evict(&mut self, removed: &Summary) {
+        if removed.count == 0 {
+            return;
+        }
+        if removed.min <= self.stats.min || removed.max >= self.stats.max {
+            self.stats = self.scan();
+        } else {
+            self.stats.sum -= removed.sum;
+            self.stats.count -= removed.count;
+        }
+    }
+
+    fn scan(&self) -> Summary {
+        self.blocks
+            .iter()
+            .fold(Summary::of(&self.tail), |acc, block| {
+                acc.merge(&block.summary())
+            })
+    }
+
+    // summary of the finite values kept, without any scanning.
+    pub fn summary(&self) -> &Summary {
+        &self.stats
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.stats.sum
+    }
+
+    // min and max of finite values.
+    pub fn min_max(&self) -> Option<(f64, f64)> {
+        self.stats.bounds()
+    }
+}
+
+impl Extend<f64> for Values {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|v| self.push_back(v));
+    }
+}
+
+impl FromIterator<f64> for Values {
+    fn from_iter<I: IntoIterator<Item = f64>>(iter: I) -> Values {
+        let mut values = Values::new();
+        values.extend(iter);
+        values
+    }
+}
+
+impl From<Vec<f64>> for Values {
+    fn from(v: Vec<f64>) -> Values {
+        v.into_iter().collect()
+    }
+}
+
+impl PartialEq<Vec<f64>> for Values {
+    fn eq(&self, other: &Vec<f64>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter().cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(n: usize) -> Vec<f64> {
+        // NaN stretches in between of regular values.
+        (0..n)
+            .map(|i| {
+                if (i / 300) % 3 == 1 {
+                    f64::NAN
+                } else {
+                    (i % 50) as f64
+                }
+            })
+            .collect()
+    }
+
+    fn same(a: impl Iterator<Item = f64>, b: &[f64]) -> bool {
+        a.map(f64::to_bits).eq(b.iter().map(|v| v.to_bits()))
+    }
+
+    #[test]
+    fn random_access() {
+        let v = sample(2000);
+        let values: Values = v.clone().into();
+        assert_eq!(values.len(), 2000);
+        assert!(same(values.iter(), &v));
+        [0, 1, 255, 256, 299, 300, 650, 1999]
+            .iter()
+            .for_each(|&i| assert_eq!(values.get(i).unwrap().to_bits(), v[i].to_bits()));
+        assert_eq!(values.get(2000), None);
+        assert!(same(values.range(250..700), &v[250..700]));
+        assert!(same(values.range(1990..2100), &v[1990..]));
+        assert_eq!(values.range(2100..2200).count(), 0);
+    }
+
+    #[test]
+    fn nan_runs() {
+        let mut values = Values::new();
+        values.extend(iter::repeat_n(f64::NAN, BLOCK * 10));
+        values.push_back(1.0);
+        assert_eq!(values.blocks.len(), 1);
+        assert_eq!(values.len(), BLOCK * 10 + 1);
+        assert!(values.get(BLOCK * 5).unwrap().is_nan());
+        assert_eq!(values.min_max(), Some((1.0, 1.0)));
+    }
+
+    #[test]
+    fn summary_and_evict() {
+        let mut v = sample(2000);
+        v[10] = -5.0;
+        v[1500] = 100.0;
+        let mut values: Values = v.clone().into();
+        assert_eq!(values.min_max(), Some((-5.0, 100.0)));
+        let sum: f64 = v.iter().filter(|v| v.is_finite()).sum();
+        assert_eq!(values.summary().sum, sum);
+        assert_eq!(values.sum(), sum);
+
+        values.evict(11);
+        assert_eq!(values.min_max(), Some((0.0, 100.0)));
+        let sum: f64 = v[11..].iter().filter(|v| v.is_finite()).sum();
+        assert_eq!(values.sum(), sum);
+        assert!(same(values.iter(), &v[11..]));
+
+        values.evict(1000);
+        assert_eq!(values.len(), 989);
+        assert!(same(values.range(0..100), &v[1011..1111]));
+        assert_eq!(values.get(489), Some(100.0));
+
+        values.evict(5000);
+        assert!(values.is_empty());
+        assert_eq!(values.min_max(), None);
+        values.push_back(3.0);
+        assert_eq!(values, vec![3.0]);
+    }
+
+    #[test]
+    fn running_summary() {
+        let mut values: Values = vec![5.0, 1.0, f64::NAN, 3.0, 4.0].into();
+        assert_eq!(values.summary().count, 4);
+        assert_eq!(values.min_max(), Some((1.0, 5.0)));
+
+        // max is evicted, so bounds are found again.
+        values.evict(1);
+        assert_eq!(values.min_max(), Some((1.0, 4.0)));
+        assert_eq!(values.sum(), 8.0);
+
+        values.evict(2);
+        assert_eq!(values.min_max(), Some((3.0, 4.0)));
+        assert_eq!(values.summary().count, 2);
+        values.push_back(-1.0);
+        assert_eq!(values.min_max(), Some((-1.0, 4.0)));
+        assert_eq!(values.sum(), 6.0);
+    }
+}
@@ -2,9 +2,21 @@ mod app;
 mod data;
 mod ui;
 
+use crate::data::aggregate::Reducer;
+use crate::data::resample::ResampleSettings;
+use crate::data::rows_config::RowsConfig;
 use crate::data::scale_config::ScalesConfig;
-use app::settings::{Column, Settings, SortingMode};
+use crate::data::series::Retention;
+use crate::data::tick::TickSettings;
+use crate::data::time::{parse_delay, parse_duration, TimeFormat, TimeParser, Zone};
+use app::ingest::Overflow;
+use app::settings::{Column, Header, Settings, SortingMode};
 use clap::{App, AppSettings, Arg, ArgGroup};
+use regex::Regex;
+use ui::depth::ColorDepth;
+use ui::glyphs::Glyphs;
+use ui::palette::{NegativeMode, Palette, MAX_BANDS};
+use ui::theme::Themes;
 
 fn main() -> Result<(), failure::Error> {
     let matches: clap::ArgMatches = App::new("hcl")
@@ -17,6 +29,21 @@ fn main() -> Result<(), failure::Error> {
                 // TODO: better help message
                 .help("use by pair format instead of csv"),
         )
+        .arg(
+            Arg::with_name("idle")
+                .long("idle")
+                .help(
+                    "pair format: complete the column if no new lines arrived within given time, e.g. 200ms.
+Useful for producers which don't print empty line after each block.",
+                )
+                .requires("p")
+                .validator(|s| {
+                    parse_duration(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("t")
                 .short("t")
@@ -28,7 +55,279 @@ fn main() -> Result<(), failure::Error> {
                 .help("name of the series to use for X axis values.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("i")
+                .short("i")
+                .help("position of the series to use for X axis values, starting from 1.")
+                .validator(|s| match s.parse::<usize>() {
+                    Ok(i) if i > 0 => Ok(()),
+                    _ => Err(format!("Bad column position: {}", s)),
+                })
+                .takes_value(true),
+        )
         .group(ArgGroup::with_name("xg").args(&["x", "i"]).required(false))
+        .arg(
+            Arg::with_name("header")
+                .long("header")
+                .help(
+                    "whether the first line of csv input is a header: always (default), or auto,
+which treats the line as data if it's all numbers.",
+                )
+                .validator(|s| match s.as_str() {
+                    "always" | "auto" => Ok(()),
+                    _ => Err(format!("Unknown header mode: {}", s)),
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no_header")
+                .long("no-header")
+                .help("csv input has no header; titles are c1..cN, unless --titles are provided.")
+                .conflicts_with("header"),
+        )
+        .arg(
+            Arg::with_name("titles")
+                .long("titles")
+                .help("comma-separated titles for csv input without header, e.g. a,b,c. Implies --no-header.")
+                .conflicts_with("header")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("only")
+                .long("only")
+                .help("keep only the series with titles matching the regex, e.g. '^cpu'.")
+                .validator(|s| Regex::new(&s).map(|_| ()).map_err(|e| format!("{}", e)))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("drop")
+                .long("drop")
+                .help("drop the series with titles matching the regex, e.g. '^(st|gu)$'.")
+                .validator(|s| Regex::new(&s).map(|_| ()).map_err(|e| format!("{}", e)))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("alias")
+                .long("alias")
+                .help(
+                    "rename the series, e.g. old=new, to keep its history when title changes in a new header.
+Can be repeated.",
+                )
+                .validator(|s| match s.split_once('=') {
+                    Some((old, new)) if !old.is_empty() && !new.is_empty() => Ok(()),
+                    _ => Err(format!("Bad alias: {}, expected old=new", s)),
+                })
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("time")
+                .short("T")
+                .long("time")
+                .help(
+                    "parse X axis values as timestamps. Format is one of:
+epoch (or s), ms, us, ns -- seconds/milliseconds/microseconds/nanoseconds since epoch;
+rfc3339                  -- e.g. 2019-10-12T07:20:50.52Z;
+strftime-like pattern    -- e.g. '%Y-%m-%d %H:%M:%S'.",
+                )
+                .requires("xg")
+                .validator(|s| {
+                    TimeFormat::new(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("utc")
+                .long("utc")
+                .help("interpret and show timestamps in UTC instead of local time zone."),
+        )
+        .arg(
+            Arg::with_name("jump")
+                .short("j")
+                .long("jump")
+                .help("time step for '[' and ']' navigation, e.g. 30s, 5m, 1h. Default is 1m.")
+                .validator(|s| parse_duration(&s).map(|_| ()).map_err(|e| format!("{}", e)))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("resample")
+                .short("r")
+                .long("resample")
+                .help(
+                    "snap timestamped rows to a fixed time grid with given step, e.g. 1s, 5m.
+Rows within the same step are aggregated, missing steps are shown as empty columns.",
+                )
+                .requires("time")
+                .validator(|s| {
+                    parse_duration(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("agg")
+                .long("agg")
+                .help(
+                    "how to aggregate rows within one step or tick: mean, sum, last, max or count.
+Default is mean.",
+                )
+                .validator(|s| {
+                    Reducer::new(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("lateness")
+                .long("lateness")
+                .help("how long to wait for out-of-order rows before emitting a step, e.g. 2s. Default is 0.")
+                .requires("resample")
+                .validator(|s| {
+                    parse_delay(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tick")
+                .long("tick")
+                .help(
+                    "make every column represent a fixed wall-clock interval, e.g. 1s.
+All rows arriving within the interval are aggregated, interval without rows becomes empty column.",
+                )
+                .conflicts_with("resample")
+                .validator(|s| {
+                    parse_duration(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("keep")
+                .long("keep")
+                .help(
+                    "how much history to keep: number of columns, e.g. 10000,
+or duration relative to the latest timestamp, e.g. 24h (requires --time or --tick). Everything is kept by default.",
+                )
+                .validator(|s| {
+                    Retention::new(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fps")
+                .long("fps")
+                .help("max number of screen updates per second. Default is 30.")
+                .validator(|s| match s.parse::<u32>() {
+                    Ok(n) if n > 0 => Ok(()),
+                    _ => Err(format!("Bad fps: {}", s)),
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("overflow")
+                .long("overflow")
+                .help(
+                    "what to do with new columns if they arrive faster than hcl handles them:
+block (slow down reading), drop or aggregate (merge them into one; not with -p).
+Default is block.",
+                )
+                .validator(|s| {
+                    Overflow::new(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("theme")
+                .long("theme")
+                .help(
+                    "color theme: light, dark, colorblind, grayscale or a user theme. Default is light.",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("themes")
+                .long("themes")
+                .help("file with user themes. Default is ~/.config/hcl/themes, if it exists.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bands")
+                .long("bands")
+                .help("number of color bands, 1..32. Palettes are generated through the theme colors.
+Default is 12 with truecolor, theme palettes otherwise. Ignored with 16 colors or less.")
+                .validator(|s| match s.parse::<usize>() {
+                    Ok(n) if n > 0 && n <= MAX_BANDS => Ok(()),
+                    _ => Err(format!("Bad number of bands: {}", s)),
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("colors")
+                .long("colors")
+                .help(
+                    "color depth of the terminal: auto, truecolor, 256, 16 or mono. Default is auto:
+detected from NO_COLOR, COLORTERM and TERM.",
+                )
+                .validator(|s| {
+                    ColorDepth::new(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("glyphs")
+                .long("glyphs")
+                .help(
+                    "characters to draw with: auto, blocks or ascii. Default is auto:
+ascii if the locale is not UTF-8.",
+                )
+                .validator(|s| {
+                    Glyphs::new(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("negative")
+                .long("negative")
+                .help(
+                    "how to draw negative values: offset (hanging from the top) or
+mirror (same as positive, in negative colors). Default is offset.",
+                )
+                .validator(|s| {
+                    NegativeMode::new(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("compact")
+                .long("compact")
+                .help("start with compact layout: one line per series, titles on the left. 'v' toggles it."),
+        )
+        .arg(
+            Arg::with_name("pause_input")
+                .long("pause-input")
+                .help(
+                    "make 'p' stop reading the input as well. By default, pause only freezes the view,
+and new data keeps being read in the background.",
+                ),
+        )
         .arg(
             Arg::with_name("scales")
                 .short("s")
@@ -71,6 +370,21 @@ another single shared autoscale.
                 })
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("rows")
+                .long("rows")
+                .help(
+                    "chart height in rows, global and per series, e.g. 2,cpu:4.
+Format is the same as for scales: series with title containing the pattern
+use the first match, others use global value. Default is 1.",
+                )
+                .validator(|s| {
+                    RowsConfig::new(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{}", e))
+                })
+                .takes_value(true),
+        )
         .arg(Arg::with_name("input_file"))
         .get_matches();
 
@@ -78,20 +392,123 @@ another single shared autoscale.
         .values_of("input_file")
         .map(|o| o.map(ToOwned::to_owned).collect());
 
+    let zone = if matches.is_present("utc") {
+        Zone::Utc
+    } else {
+        Zone::Local
+    };
+    let reducer = Reducer::new(matches.value_of("agg").unwrap_or("mean")).unwrap();
+    let tick = matches.value_of("tick").map(|t| TickSettings {
+        interval: parse_duration(t).unwrap(),
+        reducer,
+    });
+    // duration is relative to timestamps, which only -T or --tick provide.
+    let keep = matches.value_of("keep").map(|k| Retention::new(k).unwrap());
+    if let (Some(Retention::Duration(_)), false, None) = (keep, matches.is_present("time"), &tick) {
+        clap::Error::with_description(
+            "--keep with a duration requires --time or --tick",
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit()
+    }
+    // in pair format every column comes as a set, and sets are never
+    // dropped or merged.
+    if let (true, Some(overflow @ ("drop" | "aggregate"))) =
+        (matches.is_present("p"), matches.value_of("overflow"))
+    {
+        clap::Error::with_description(
+            &format!("--overflow {} can't be used with -p", overflow),
+            clap::ErrorKind::ArgumentConflict,
+        )
+        .exit()
+    }
+
+    // user themes are only known after reading the file.
+    let mut themes = Themes::new();
+    let theme = themes
+        .load_file(matches.value_of("themes"))
+        .and_then(|_| themes.get(matches.value_of("theme").unwrap_or("light")))
+        .unwrap_or_else(|e| {
+            clap::Error::with_description(&format!("{}", e), clap::ErrorKind::InvalidValue).exit()
+        });
+    let depth = ColorDepth::new(matches.value_of("colors").unwrap_or("auto")).unwrap();
+    let theme = depth.theme(&theme);
+
     let settings = Settings {
         input_file,
         scales: matches.value_of("scales").map(ToOwned::to_owned),
-        x: match matches.value_of("x") {
-            Some(title) => Column::Title(title.to_owned()),
+        rows: matches.value_of("rows").map(ToOwned::to_owned),
+        compact: matches.is_present("compact"),
+        palette: Palette::new(
+            &theme,
+            matches
+                .value_of("bands")
+                .map(|b| b.parse::<usize>().unwrap()),
+            NegativeMode::new(matches.value_of("negative").unwrap_or("offset")).unwrap(),
+            depth,
+        )
+        .unwrap(),
+        theme,
+        glyphs: Glyphs::new(matches.value_of("glyphs").unwrap_or("auto")).unwrap(),
+        x: match (matches.value_of("x"), matches.value_of("i")) {
+            (Some(title), _) => Column::Title(title.to_owned()),
+            (_, Some(i)) => Column::Index(i.parse::<usize>().unwrap() - 1),
             _ => Column::None,
         },
+        header: match matches.value_of("titles") {
+            Some(titles) => {
+                Header::Absent(Some(titles.split(',').map(ToOwned::to_owned).collect()))
+            }
+            None if matches.is_present("no_header") => Header::Absent(None),
+            None if matches.value_of("header") == Some("auto") => Header::Auto,
+            None => Header::Present,
+        },
+        only: matches.value_of("only").map(|r| Regex::new(r).unwrap()),
+        drop: matches.value_of("drop").map(|r| Regex::new(r).unwrap()),
+        aliases: matches
+            .values_of("alias")
+            .map(|a| {
+                a.filter_map(|a| a.split_once('='))
+                    .map(|(old, new)| (old.to_owned(), new.to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        time: match matches.value_of("time") {
+            Some(t) => Some(TimeParser::new(TimeFormat::new(t).unwrap(), zone)),
+            // in tick mode x axis is wall-clock time of the tick.
+            None if tick.is_some() => Some(TimeParser::new(TimeFormat::EpochNanos, zone)),
+            None => None,
+        },
+        jump: parse_duration(matches.value_of("jump").unwrap_or("1m")).unwrap(),
+        resample: matches.value_of("resample").map(|r| ResampleSettings {
+            step: parse_duration(r).unwrap(),
+            reducer,
+            lateness: matches
+                .value_of("lateness")
+                .map(|l| parse_delay(l).unwrap())
+                .unwrap_or(0),
+        }),
+        tick,
+        keep,
         paired: matches.is_present("p"),
+        pause_input: matches.is_present("pause_input"),
+        idle: matches.value_of("idle").map(|i| parse_duration(i).unwrap()),
         sort_mode: if matches.is_present("t") {
             SortingMode::TitlesNumericAsc
         } else {
             SortingMode::ValuesDesc
         },
+        fps: matches
+            .value_of("fps")
+            .map(|f| f.parse::<u32>().unwrap())
+            .unwrap_or(30),
+        overflow: Overflow::new(matches.value_of("overflow").unwrap_or("block")).unwrap(),
     };
 
-    app::event_loop::EventLoop::start(settings)
+    // terminal is restored by the time start returns.
+    let status = app::event_loop::EventLoop::start(settings)?;
+    if status != 0 {
+        std::process::exit(status);
+    }
+    Ok(())
 }
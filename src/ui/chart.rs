@@ -1,7 +1,13 @@
+use crate::app::window::fit;
 use crate::data::scale::Scale;
 use crate::data::state::State;
+use crate::data::unit::format_value;
 use crate::ui::column::Column;
-use crate::ui::style::{default, EmptyBox};
+use crate::ui::glyphs::Glyphs;
+use crate::ui::gutter::{elide, gutter_width, width, Affixes};
+use crate::ui::palette::Palette;
+use crate::ui::style::EmptyBox;
+use crate::ui::theme::Theme;
 
 use tui::buffer::Buffer;
 use tui::layout::Rect;
@@ -9,28 +15,47 @@ use tui::widgets::Widget;
 
 pub struct Charts<'a> {
     state: &'a State,
+    theme: &'a Theme,
+    palette: &'a Palette,
+    glyphs: Glyphs,
 }
 
 impl<'a> Charts<'a> {
-    pub const SERIES_HEIGHT: i64 = 2;
-    pub fn new(state: &'a State) -> Self {
-        Charts { state }
+    pub fn new(state: &'a State, theme: &'a Theme, palette: &'a Palette, glyphs: Glyphs) -> Self {
+        Charts {
+            state,
+            theme,
+            palette,
+            glyphs,
+        }
     }
 }
 
 impl<'a> Widget for Charts<'a> {
     fn draw(&mut self, area: Rect, buf: &mut Buffer) {
-        EmptyBox::fill(area, buf);
+        EmptyBox::fill(area, buf, self.theme.text());
+        let compact = self.state.is_compact();
+        let gutter = gutter_width(compact);
+        if area.width <= gutter {
+            return;
+        }
 
-        // effective width, as 1 character is used for the markers.
-        let w = area.width - 1;
+        // effective width, without markers or titles on the left.
+        let w = area.width - gutter;
+        let left = area.left() + gutter;
 
+        let (text, cursor_style) = (self.theme.text(), self.theme.cursor());
+        // Label is on the right of the cursor in the left half of the chart,
+        // and on the left otherwise, if it fits; it is cut at the right edge.
+        let right = area.right();
         let render_cursor = |x: u16, y: u16, label: &str, symbol: &str, buf: &mut Buffer| {
-            if x * 2 < w {
-                buf.set_string(x, y, format!("{}{}", symbol, label), default());
+            let before = format!("{}{}", label, symbol);
+            let len = width(&before) as u16;
+            if (x - left) * 2 >= w && len <= x - left + 1 {
+                buf.set_string(x + 1 - len, y, before, cursor_style);
             } else {
-                let cursor = format!("{}{}", label, symbol);
-                buf.set_string(x - cursor.len() as u16 + 1, y, cursor, default());
+                let after = format!("{}{}", symbol, label);
+                buf.set_stringn(x, y, after, (right - x) as usize, cursor_style);
             }
         };
 
@@ -38,77 +63,252 @@ impl<'a> Widget for Charts<'a> {
 
         let scales = self.state.scales.as_ref().map(|s| s.materialize(&data.y));
 
-        // charts
+        // titles in compact layout don't repeat what all of them share.
+        let affixes = if compact {
+            Affixes::common(data.y.iter().map(|s| s.title.as_str()))
+        } else {
+            Affixes::default()
+        };
+        let cursor = self.state.x.offset + self.state.x.cursor;
+
+        // charts; every series takes title row and its chart rows,
+        // or just chart rows in compact layout.
+        let offset = self.state.y.offset as usize;
+        let heights = self.state.heights();
+        let shown = fit(heights.iter().skip(offset), area.height as i64 - 1) as usize;
+        let mut y = area.top();
         data.y
             .iter()
-            .skip(self.state.y.offset as usize)
-            .take(area.height as usize / 2)
-            .enumerate()
-            .for_each(|(i, series)| {
+            .zip(heights.iter())
+            .skip(offset)
+            .take(shown)
+            .for_each(|(series, height)| {
+                let (rows, top) = if compact {
+                    (*height as u16, y)
+                } else {
+                    (*height as u16 - 1, y + 1)
+                };
                 let scale = scales
                     .as_ref()
                     .and_then(|scales| scales.pick(&series.title))
-                    .unwrap_or_else(|| Scale::auto(&series.values[..]));
-
-                let y = area.top() + i as u16 * 2;
+                    .unwrap_or_else(|| {
+                        Scale::auto_for_unit(series.values.min_max(), series.unit.as_ref())
+                    });
 
-                buf.set_string(area.left(), y, format!("┌{}", series.title), default());
-                buf.set_string(area.left(), y + 1, "└", default());
+                if compact {
+                    // value under the cursor on the right side of the gutter.
+                    // value is elided as well, if it doesn't fit.
+                    let value = match series.values.get(cursor as usize) {
+                        Some(v) if self.state.cursor_allowed() => {
+                            format_value(v, series.unit.as_ref())
+                        }
+                        _ => String::new(),
+                    };
+                    let value = elide(&value, gutter as usize - 1, self.glyphs.ellipsis());
+                    let space = (gutter as usize - 1).saturating_sub(width(&value) + 1);
+                    let title = elide(affixes.strip(&series.title), space, self.glyphs.ellipsis());
+                    buf.set_string(area.left(), y, &title, text);
+                    buf.set_string(
+                        (left - 1).saturating_sub(width(&value) as u16),
+                        y,
+                        &value,
+                        text,
+                    );
+                } else {
+                    let (first, middle, last) = self.glyphs.markers();
+                    buf.set_string(area.left(), y, format!("{}{}", first, series.title), text);
+                    for r in 1..rows {
+                        buf.set_string(area.left(), y + r, middle, text);
+                    }
+                    buf.set_string(area.left(), y + rows, last, text);
+                }
 
+                let from = self.state.x.offset as usize;
                 series
                     .values
-                    .iter()
-                    .skip(self.state.x.offset as usize)
-                    .take(w as usize)
+                    .range(from..from + w as usize)
                     .enumerate()
                     .for_each(|(j, v)| {
-                        let c = Column::from_value(scale.run(*v));
-                        buf.get_mut(area.left() + 1 + j as u16, y + 1)
-                            .set_style(c.style)
-                            .set_char(c.symbol);
+                        let column = Column::from_value(
+                            scale.run(v),
+                            rows as usize,
+                            self.palette,
+                            self.glyphs,
+                        );
+                        for (r, c) in column.into_iter().enumerate() {
+                            buf.get_mut(left + j as u16, top + r as u16)
+                                .set_style(c.style)
+                                .set_char(c.symbol);
+                        }
 
                         // draw cursor
-                        if j == self.state.x.cursor as usize && self.state.cursor_allowed() {
+                        if !compact
+                            && j == self.state.x.cursor as usize
+                            && self.state.cursor_allowed()
+                        {
                             render_cursor(
-                                area.left() + 1 + j as u16,
+                                left + j as u16,
                                 y,
-                                &format!("{:.3}", *v),
+                                &format_value(v, series.unit.as_ref()),
                                 "|",
                                 buf,
                             );
                         }
                     });
+                y += *height as u16;
             });
 
         // x axis
         if let Some((_, x)) = &data.x {
             let from = self.state.x.offset as usize;
             let to = std::cmp::min(self.state.x.offset as usize + w as usize, x.len());
-            let xx = &x[from..to];
-            if let Some(xv) = xx.first() {
+            // number of visible x values
+            let visible = to.saturating_sub(from);
+
+            // with timestamps available, edge labels are formatted to fit
+            // the visible time span, and cursor shows full timestamp.
+            let time = self.state.time.as_ref();
+            let span = match (
+                data.time_at(from),
+                to.checked_sub(1).and_then(|i| data.time_at(i)),
+            ) {
+                (Some(a), Some(b)) => b - a,
+                _ => 0,
+            };
+            let edge_label = |i: usize| match (time, data.time_at(i)) {
+                (Some(p), Some(t)) => p.format_for_span(t, span),
+                _ => x[i].clone(),
+            };
+            let full_label = |i: usize| match (time, data.time_at(i)) {
+                (Some(p), Some(t)) => p.format_full(t),
+                _ => x[i].clone(),
+            };
+
+            if visible > 0 {
                 let symbol = if from > 0 { "<" } else { "|" };
-                render_cursor(area.left() + 1, area.bottom() - 1, xv, symbol, buf);
-            }
-            if let Some(xv) = xx.last() {
+                render_cursor(left, area.bottom() - 1, &edge_label(from), symbol, buf);
+
                 let symbol = if to < x.len() { ">" } else { "|" };
                 render_cursor(
-                    area.left() + xx.len() as u16,
+                    left + visible as u16 - 1,
                     area.bottom() - 1,
-                    xv,
+                    &edge_label(to - 1),
                     symbol,
                     buf,
                 );
             }
 
-            if xx.len() > self.state.x.cursor as usize && self.state.cursor_allowed() {
+            if visible > self.state.x.cursor as usize && self.state.cursor_allowed() {
                 render_cursor(
-                    area.left() + 1 + self.state.x.cursor as u16,
+                    left + self.state.x.cursor as u16,
                     area.bottom() - 1,
-                    &xx[self.state.x.cursor as usize],
+                    &full_label(from + self.state.x.cursor as usize),
                     "|",
                     buf,
                 );
             }
+        } else if compact && self.state.cursor_allowed() && cursor < data.series_size() {
+            // there are no cursor markers above the charts in compact layout.
+            render_cursor(
+                left + self.state.x.cursor as u16,
+                area.bottom() - 1,
+                "",
+                "|",
+                buf,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::ingest::Overflow;
+    use crate::app::settings::{Column, Header, Settings, SortingMode};
+    use crate::data::series::{Series, SeriesSet};
+    use crate::data::time::{TimeFormat, TimeParser, Zone};
+    use std::collections::{HashMap, VecDeque};
+
+    fn state(compact: bool) -> State {
+        let settings = Settings {
+            input_file: None,
+            x: Column::Index(0),
+            header: Header::Auto,
+            only: None,
+            drop: None,
+            aliases: HashMap::new(),
+            time: Some(TimeParser::new(TimeFormat::EpochSeconds, Zone::Utc)),
+            jump: 1_000_000_000,
+            resample: None,
+            tick: None,
+            keep: None,
+            scales: None,
+            rows: None,
+            compact,
+            theme: Theme::default(),
+            palette: Palette::default(),
+            glyphs: Glyphs::Blocks,
+            paired: false,
+            pause_input: false,
+            idle: None,
+            sort_mode: SortingMode::ValuesDesc,
+            fps: 30,
+            overflow: Overflow::Block,
+        };
+        let mut state = State::from_settings(&settings);
+        let x: VecDeque<String> = (0..40).map(|i| (1_700_000_000 + i).to_string()).collect();
+        let t = x
+            .iter()
+            .map(|x| settings.time.as_ref().unwrap().parse(x))
+            .collect();
+        let mut series = Series::with_title("requests_per_second_total");
+        (0..39).for_each(|i| series.values.push_back(1e12 * i as f64));
+        series.values.push_back(1e300);
+        state.data = SeriesSet::new(Some(("time".to_owned(), x)), Some(t), vec![series]);
+        state
+    }
+
+    fn draw(state: &State) -> Buffer {
+        let area = Rect::new(0, 0, 30, 6);
+        let mut buf = Buffer::empty(area);
+        let (theme, palette) = (Theme::default(), Palette::default());
+        Charts::new(state, &theme, &palette, Glyphs::Blocks).draw(area, &mut buf);
+        buf
+    }
+
+    fn line(buf: &Buffer, y: u16) -> String {
+        (0..buf.area.width)
+            .map(|x| buf.get(x, y).symbol.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn long_cursor_labels() {
+        let mut state = state(false);
+        for cursor in 0..29 {
+            state.x.cursor = cursor;
+            let buf = draw(&state);
+            // full timestamp doesn't fit on the left, and is cut on the right.
+            let axis = line(&buf, 5);
+            assert!(axis.contains('|'));
+            if cursor == 15 {
+                assert_eq!(&axis[16..], "|2023-11-14 22");
+            }
         }
+        // huge value at the cursor is in exponent form.
+        state.x.offset = 20;
+        state.x.cursor = 19;
+        let buf = draw(&state);
+        assert_eq!(line(&buf, 0), "┌requests_p1.000e300|total    ");
+    }
+
+    #[test]
+    fn long_compact_values() {
+        let mut state = state(true);
+        state.x.cursor = 39;
+        let buf = draw(&state);
+        // huge value is short enough to keep the title.
+        let gutter: String = line(&buf, 0).chars().take(20).collect();
+        assert_eq!(gutter, "requ…otal 1.000e300 ");
     }
 }
@@ -1,26 +1,7 @@
-use crate::ui::style::default;
-use tui::style::Color;
-use tui::style::Style;
-
-// Eventually might need to configure these
-const GREEN: [Color; 6] = [
-    Color::Indexed(231),
-    Color::Indexed(194),
-    Color::Indexed(150),
-    Color::Indexed(107),
-    Color::Indexed(64),
-    Color::Indexed(22),
-];
-const RED: [Color; 6] = [
-    Color::Indexed(231),
-    Color::Indexed(224),
-    Color::Indexed(181),
-    Color::Indexed(131),
-    Color::Indexed(88),
-    Color::Indexed(52),
-];
-
-const SYMBOLS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+use crate::ui::depth::ColorDepth;
+use crate::ui::glyphs::Glyphs;
+use crate::ui::palette::{NegativeMode, Palette};
+use tui::style::{Modifier, Style};
 
 pub struct Column {
     pub style: Style,
@@ -28,24 +9,33 @@ pub struct Column {
 }
 
 impl Column {
-    fn from_value_impl(
-        v: f64,
-        symbols: &[char],
-        positive_palette: &[Color],
-        negative_palette: &[Color],
-    ) -> Column {
+    // Column of given number of rows, top to bottom. The band within
+    // the color is split between the rows, so each row adds
+    // glyph levels - 1 of vertical resolution.
+    pub fn from_value(v: f64, rows: usize, palette: &Palette, glyphs: Glyphs) -> Vec<Column> {
+        let blank = palette.blank;
         if v.is_nan() {
-            return Column {
-                style: default(),
-                symbol: '.',
-            };
+            let mut res: Vec<Column> = (1..rows)
+                .map(|_| Column {
+                    style: blank,
+                    symbol: ' ',
+                })
+                .collect();
+            res.push(Column {
+                style: blank,
+                symbol: glyphs.missing(),
+            });
+            return res;
         }
-        let v = v.min(1.0).max(-1.0);
-        let column_height = symbols.len() - 1;
+        let v = v.clamp(-1.0, 1.0);
+        let symbols = glyphs.levels();
+        let row_height = symbols.len() - 1;
+        let column_height = row_height * rows;
+        let (negative_mode, depth) = (palette.negative_mode, palette.depth);
         let palette = if v < 0.0 {
-            &negative_palette
+            &palette.negative
         } else {
-            &positive_palette
+            &palette.positive
         };
         let scaled_value = v.abs() * (palette.len() - 1) as f64;
         let mut color_index = scaled_value.div_euclid(1.0).round() as usize;
@@ -54,22 +44,119 @@ impl Column {
         // empty at color C == full at color (C-1)
         if color_index + 1 == palette.len() && scaled_value == 0 {
             color_index -= 1;
-            scaled_value = symbols.len() - 1;
+            scaled_value = column_height;
         }
+        // in offset mode negative values hang from the top.
+        // Blocks are turned upside down by swapping the colors; ascii
+        // levels only fill the rows from the top.
+        let hanging = v < 0.0 && negative_mode == NegativeMode::Offset;
+        let swapped = hanging && glyphs.fill_bottom();
         let mut bg = palette[color_index];
         let mut fg = palette[color_index + 1];
-        if v < 0.0 {
+        if swapped {
             std::mem::swap(&mut bg, &mut fg);
-            scaled_value = column_height - scaled_value;
         }
+        // there are no colors to tell the sign in monochrome.
+        let style = if hanging && depth == ColorDepth::Mono {
+            Style::default().modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        let style = style.bg(bg).fg(fg);
 
-        Column {
-            style: Style::default().bg(bg).fg(fg),
-            symbol: symbols[scaled_value],
+        let mut res: Vec<Column> = (0..rows)
+            .map(|i| {
+                let level = scaled_value.saturating_sub(i * row_height).min(row_height);
+                Column {
+                    style,
+                    symbol: if swapped {
+                        symbols[row_height - level]
+                    } else {
+                        symbols[level]
+                    },
+                }
+            })
+            .collect();
+        if !hanging {
+            res.reverse();
         }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::theme::Theme;
+
+    fn symbols(v: f64, rows: usize) -> String {
+        Column::from_value(v, rows, &Palette::default(), Glyphs::Blocks)
+            .into_iter()
+            .map(|c| c.symbol)
+            .collect()
     }
 
-    pub fn from_value(v: f64) -> Column {
-        Column::from_value_impl(v, &SYMBOLS, &GREEN, &RED)
+    #[test]
+    fn stacked_rows() {
+        assert_eq!(symbols(0.1, 1), "▄");
+        // 0.1 is half of the first band.
+        assert_eq!(symbols(0.1, 2), " █");
+        assert_eq!(symbols(0.1, 4), "  ██");
+        // negative values fill rows with darker background from the top.
+        assert_eq!(symbols(-0.1, 2), " █");
+        assert_eq!(symbols(-0.1, 1), "▄");
+        assert_eq!(symbols(f64::NAN, 3), "  .");
+    }
+
+    #[test]
+    fn mirror() {
+        let palette = Palette::new(
+            &Theme::default(),
+            None,
+            NegativeMode::Mirror,
+            ColorDepth::Colors256,
+        )
+        .unwrap();
+        let column = Column::from_value(-0.1, 2, &palette, Glyphs::Blocks);
+        let symbols: String = column.iter().map(|c| c.symbol).collect();
+        assert_eq!(symbols, " █");
+        assert_eq!(column[1].style.fg, palette.negative[1]);
+    }
+
+    #[test]
+    fn mono() {
+        let depth = ColorDepth::Mono;
+        let palette = Palette::new(
+            &depth.theme(&Theme::default()),
+            None,
+            NegativeMode::Mirror,
+            depth,
+        )
+        .unwrap();
+        // magnitude is only shown by the glyphs.
+        let column = Column::from_value(0.5, 1, &palette, Glyphs::Blocks);
+        assert_eq!(column[0].symbol, '▄');
+        let column = Column::from_value(-0.5, 1, &palette, Glyphs::Blocks);
+        assert_eq!(column[0].symbol, '▄');
+        assert!(column[0].style.modifier.contains(Modifier::REVERSED));
+    }
+
+    #[test]
+    fn ascii() {
+        let palette = Palette::default();
+        let symbols = |v: f64, rows: usize| -> String {
+            Column::from_value(v, rows, &palette, Glyphs::Ascii)
+                .into_iter()
+                .map(|c| c.symbol)
+                .collect()
+        };
+        // 9 levels per row.
+        assert_eq!(symbols(0.1, 1), "+");
+        assert_eq!(symbols(0.1, 2), " @");
+        // hanging values keep the colors, and fill rows from the top.
+        assert_eq!(symbols(-0.1, 2), "@ ");
+        let column = Column::from_value(-0.1, 1, &palette, Glyphs::Ascii);
+        assert_eq!(column[0].style.fg, palette.negative[1]);
+        assert_eq!(symbols(f64::NAN, 2), " _");
     }
 }
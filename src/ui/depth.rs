@@ -0,0 +1,322 @@
+use crate::ui::theme::{rgb, Theme};
+
+use std::env;
+use std::io::{self, Write};
+
+use regex::bytes::{Captures, Regex};
+use tui::style::Color;
+
+/// ColorDepth is how many colors the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorDepth {
+    TrueColor,
+    Colors256,
+    Colors16,
+    // no colors at all; magnitude is shown with glyphs only.
+    Mono,
+}
+
+// terminals known to have no more than 16 colors.
+const BASIC_TERMS: [&str; 6] = [
+    "linux",
+    "ansi",
+    "cons25",
+    "cygwin",
+    "xterm-color",
+    "xterm-16color",
+];
+
+// terminals without colors at all.
+const MONO_TERMS: [&str; 3] = ["dumb", "vt100", "vt220"];
+
+impl ColorDepth {
+    // 'auto' detects the depth from the environment.
+    pub fn new(conf: &str) -> Result<ColorDepth, DepthError> {
+        match conf {
+            "auto" => Ok(ColorDepth::detect(
+                env::var("NO_COLOR").ok().as_deref(),
+                env::var("COLORTERM").ok().as_deref(),
+                env::var("TERM").ok().as_deref(),
+            )),
+            "truecolor" => Ok(ColorDepth::TrueColor),
+            "256" => Ok(ColorDepth::Colors256),
+            "16" => Ok(ColorDepth::Colors16),
+            "mono" => Ok(ColorDepth::Mono),
+            _ => Err(DepthError::BadDepth(conf.to_owned())),
+        }
+    }
+
+    // Terminals which don't advertise their depth, or don't set TERM at
+    // all, are assumed to have 256 colors, unless they are known to
+    // have less.
+    fn detect(no_color: Option<&str>, colorterm: Option<&str>, term: Option<&str>) -> ColorDepth {
+        if no_color.is_some_and(|v| !v.is_empty()) {
+            return ColorDepth::Mono;
+        }
+        if matches!(colorterm, Some("truecolor") | Some("24bit")) {
+            return ColorDepth::TrueColor;
+        }
+        match term {
+            None | Some("") => ColorDepth::Colors256,
+            Some(t) if MONO_TERMS.contains(&t) => ColorDepth::Mono,
+            Some(t) if t.ends_with("-direct") => ColorDepth::TrueColor,
+            Some(t) if t.contains("256color") => ColorDepth::Colors256,
+            Some(t) if BASIC_TERMS.contains(&t) => ColorDepth::Colors16,
+            Some(_) => ColorDepth::Colors256,
+        }
+    }
+
+    // closest color the terminal can show; 16 colors are handled for
+    // the whole theme, see theme().
+    pub fn quantize(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorDepth::Colors256, Color::Rgb(..)) => to_indexed(rgb(color)),
+            (ColorDepth::Mono, _) => Color::Reset,
+            _ => color,
+        }
+    }
+
+    // Theme colors the terminal can show. With 16 colors, palettes are
+    // reduced to 2 bands of the named color closest to the theme's.
+    pub fn theme(self, theme: &Theme) -> Theme {
+        match self {
+            ColorDepth::TrueColor => theme.clone(),
+            ColorDepth::Colors256 => Theme {
+                background: self.quantize(theme.background),
+                text: self.quantize(theme.text),
+                cursor: self.quantize(theme.cursor),
+                positive: theme.positive.iter().map(|c| self.quantize(*c)).collect(),
+                negative: theme.negative.iter().map(|c| self.quantize(*c)).collect(),
+            },
+            ColorDepth::Colors16 => {
+                let light = luminance(rgb(theme.background)) > 0.5;
+                let (background, text) = if light {
+                    (Color::White, Color::Black)
+                } else {
+                    (Color::Black, Color::White)
+                };
+                let palette = |p: &[Color]| {
+                    let (dark, bright) = named(rgb(p[p.len() - 1]));
+                    if light {
+                        vec![background, bright, dark]
+                    } else {
+                        vec![background, dark, bright]
+                    }
+                };
+                Theme {
+                    background,
+                    text,
+                    cursor: text,
+                    positive: palette(&theme.positive),
+                    negative: palette(&theme.negative),
+                }
+            }
+            ColorDepth::Mono => Theme {
+                background: Color::Reset,
+                text: Color::Reset,
+                cursor: Color::Reset,
+                positive: vec![Color::Reset; 2],
+                negative: vec![Color::Reset; 2],
+            },
+        }
+    }
+}
+
+// closest color of 6x6x6 cube of 256-color palette.
+pub fn to_indexed((r, g, b): (u8, u8, u8)) -> Color {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |c: u8| {
+        (0..LEVELS.len())
+            .min_by_key(|i| (LEVELS[*i] as i32 - c as i32).abs())
+            .unwrap() as u8
+    };
+    Color::Indexed(16 + 36 * level(r) + 6 * level(g) + level(b))
+}
+
+fn luminance((r, g, b): (u8, u8, u8)) -> f64 {
+    (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64) / 255.0
+}
+
+// dark and bright named colors of the same hue.
+fn named((r, g, b): (u8, u8, u8)) -> (Color, Color) {
+    let (r, g, b) = (r as f64, g as f64, b as f64);
+    let max = r.max(g).max(b);
+    let chroma = max - r.min(g).min(b);
+    if chroma < 40.0 {
+        return (Color::DarkGray, Color::Gray);
+    }
+    let hue = if max == r {
+        60.0 * ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / chroma + 2.0)
+    } else {
+        60.0 * ((r - g) / chroma + 4.0)
+    };
+    match ((hue + 30.0) / 60.0) as usize % 6 {
+        0 => (Color::Red, Color::LightRed),
+        1 => (Color::Yellow, Color::LightYellow),
+        2 => (Color::Green, Color::LightGreen),
+        3 => (Color::Cyan, Color::LightCyan),
+        4 => (Color::Blue, Color::LightBlue),
+        _ => (Color::Magenta, Color::LightMagenta),
+    }
+}
+
+/// BasicColors passes the output to the terminal, rewriting the 16 named
+/// colors to SGR 30-37, 90-97 (and 40-47, 100-107 for background).
+/// Termion emits them as 256-color escapes, e.g. '38;5;1' for red,
+/// which 16-color terminals don't understand.
+pub struct BasicColors<W: Write> {
+    inner: W,
+    // only rewritten for 16 colors; other depths pass the output as is.
+    enabled: bool,
+    escape: Regex,
+    // escape sequence which was cut at the end of the previous write.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> BasicColors<W> {
+    pub fn new(inner: W, depth: ColorDepth) -> BasicColors<W> {
+        BasicColors {
+            inner,
+            enabled: depth == ColorDepth::Colors16,
+            escape: Regex::new(r"\x1b\[(3|4)8;5;(\d+)m").unwrap(),
+            pending: vec![],
+        }
+    }
+
+    // length of the output up to the escape sequence which is not complete yet.
+    fn complete(&self) -> usize {
+        let buf = &self.pending;
+        match buf.iter().rposition(|&b| b == 0x1b) {
+            Some(i)
+                if !buf[i + 1..]
+                    .iter()
+                    .any(|b| (0x40..=0x7e).contains(b) && *b != b'[') =>
+            {
+                i
+            }
+            _ => buf.len(),
+        }
+    }
+}
+
+impl<W: Write> Write for BasicColors<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.enabled {
+            return self.inner.write(buf);
+        }
+        self.pending.extend_from_slice(buf);
+        let rest = self.pending.split_off(self.complete());
+        let out = self.escape.replace_all(&self.pending, |c: &Captures| {
+            let n: u8 = std::str::from_utf8(&c[2])
+                .unwrap()
+                .parse()
+                .unwrap_or(u8::MAX);
+            match (n, &c[1]) {
+                (0..=7, b"3") => format!("\x1b[{}m", 30 + n).into_bytes(),
+                (8..=15, b"3") => format!("\x1b[{}m", 90 + n - 8).into_bytes(),
+                (0..=7, _) => format!("\x1b[{}m", 40 + n).into_bytes(),
+                (8..=15, _) => format!("\x1b[{}m", 100 + n - 8).into_bytes(),
+                _ => c[0].to_vec(),
+            }
+        });
+        self.inner.write_all(&out)?;
+        self.pending = rest;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.write_all(&std::mem::take(&mut self.pending))?;
+        self.inner.flush()
+    }
+}
+
+#[derive(Debug)]
+pub enum DepthError {
+    BadDepth(String),
+}
+
+impl std::fmt::Display for DepthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            DepthError::BadDepth(ref s) => write!(f, "Unknown color depth: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        let detect = ColorDepth::detect;
+        assert_eq!(
+            detect(Some("1"), Some("truecolor"), Some("xterm")),
+            ColorDepth::Mono
+        );
+        assert_eq!(
+            detect(Some(""), Some("truecolor"), Some("xterm")),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(
+            detect(None, None, Some("xterm-256color")),
+            ColorDepth::Colors256
+        );
+        assert_eq!(detect(None, None, Some("linux")), ColorDepth::Colors16);
+        assert_eq!(detect(None, None, Some("dumb")), ColorDepth::Mono);
+        assert_eq!(detect(None, None, Some("vt100")), ColorDepth::Mono);
+        assert_eq!(detect(None, None, None), ColorDepth::Colors256);
+        assert_eq!(detect(None, None, Some("")), ColorDepth::Colors256);
+        assert_eq!(detect(Some("1"), None, None), ColorDepth::Mono);
+        assert!(ColorDepth::new("8").is_err());
+    }
+
+    #[test]
+    fn reduced_themes() {
+        let theme = ColorDepth::Colors16.theme(&Theme::light());
+        assert_eq!(
+            theme.positive,
+            vec![Color::White, Color::LightGreen, Color::Green]
+        );
+        assert_eq!(theme.negative[2], Color::Red);
+        let theme = ColorDepth::Colors16.theme(&Theme::colorblind());
+        assert_eq!(theme.positive[2], Color::Blue);
+        assert_eq!(theme.negative[2], Color::Yellow);
+        let theme = ColorDepth::Colors16.theme(&Theme::dark());
+        assert_eq!(
+            theme.positive,
+            vec![Color::Black, Color::Green, Color::LightGreen]
+        );
+
+        let mut theme = Theme::light();
+        theme.background = Color::Rgb(0, 0, 135);
+        assert_eq!(
+            ColorDepth::Colors256.theme(&theme).background,
+            Color::Indexed(18)
+        );
+    }
+
+    #[test]
+    fn basic_colors() {
+        use termion::color::{AnsiValue, Bg, Fg, LightGreen, Red, Reset};
+
+        let mut out = BasicColors::new(vec![], ColorDepth::Colors16);
+        let theme = format!("{}{}x{}", Fg(Red), Bg(LightGreen), Fg(Reset));
+        // sequence cut between writes is rewritten as well.
+        let indexed = format!("{}{}", Bg(AnsiValue(4)), Fg(AnsiValue(18)));
+        out.write_all(theme.as_bytes()).unwrap();
+        out.write_all(&indexed.as_bytes()[..4]).unwrap();
+        out.write_all(&indexed.as_bytes()[4..]).unwrap();
+        out.flush().unwrap();
+        assert_eq!(
+            String::from_utf8(out.inner).unwrap(),
+            "\x1b[31m\x1b[102mx\x1b[39m\x1b[44m\x1b[38;5;18m"
+        );
+
+        let mut out = BasicColors::new(vec![], ColorDepth::Colors256);
+        out.write_all(format!("{}", Fg(Red)).as_bytes()).unwrap();
+        assert_eq!(out.inner, b"\x1b[38;5;1m");
+    }
+}
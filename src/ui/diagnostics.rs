@@ -0,0 +1,73 @@
+use crate::data::diagnostics::Diagnostics;
+use crate::ui::style::EmptyBox;
+use crate::ui::theme::Theme;
+
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::Modifier;
+use tui::widgets::Widget;
+
+/// Diagnostics pane shows the summary of ingestion problems:
+/// parse failures per series and the last rejected lines.
+pub struct DiagnosticsPane<'a> {
+    diagnostics: &'a Diagnostics,
+    theme: &'a Theme,
+}
+
+impl<'a> DiagnosticsPane<'a> {
+    pub fn new(diagnostics: &'a Diagnostics, theme: &'a Theme) -> Self {
+        DiagnosticsPane { diagnostics, theme }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let d = self.diagnostics;
+        let mut lines = vec![format!(
+            "{} lines read, {} rejected ('e' to close)",
+            d.lines, d.rejected
+        )];
+        if d.out_of_range > 0 {
+            lines.push(format!(
+                "{} samples dropped by resampling: late or too far ahead",
+                d.out_of_range
+            ));
+        }
+        if !d.failures.is_empty() {
+            let failures: Vec<String> = d
+                .failures
+                .iter()
+                .map(|(title, n)| format!("{}: {}", title, n))
+                .collect();
+            lines.push(format!("parse failures: {}", failures.join(", ")));
+        }
+        lines.push(String::new());
+        // most recent first
+        d.last.iter().rev().for_each(|r| {
+            lines.push(format!("line {}: {}: {}", r.line, r.reason, r.text));
+        });
+        lines
+    }
+}
+
+impl<'a> Widget for DiagnosticsPane<'a> {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        EmptyBox::fill(area, buf, self.theme.text());
+        self.lines()
+            .iter()
+            .take(area.height as usize)
+            .enumerate()
+            .for_each(|(i, line)| {
+                let style = if i == 0 {
+                    self.theme.text().modifier(Modifier::BOLD)
+                } else {
+                    self.theme.text()
+                };
+                buf.set_stringn(
+                    area.left(),
+                    area.top() + i as u16,
+                    line,
+                    area.width as usize,
+                    style,
+                );
+            });
+    }
+}
@@ -0,0 +1,105 @@
+use std::env;
+
+/// Glyphs are the characters charts are drawn with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Glyphs {
+    // unicode block elements and box drawing.
+    Blocks,
+    // for fonts and terminals without them.
+    Ascii,
+}
+
+const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const ASCII: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+impl Glyphs {
+    // 'auto' picks ascii if the locale is not UTF-8.
+    pub fn new(conf: &str) -> Result<Glyphs, GlyphsError> {
+        match conf {
+            "auto" => Ok(Glyphs::detect(
+                ["LC_ALL", "LC_CTYPE", "LANG"]
+                    .iter()
+                    .filter_map(|v| env::var(v).ok())
+                    .find(|v| !v.is_empty())
+                    .as_deref(),
+            )),
+            "blocks" => Ok(Glyphs::Blocks),
+            "ascii" => Ok(Glyphs::Ascii),
+            _ => Err(GlyphsError::BadGlyphs(conf.to_owned())),
+        }
+    }
+
+    // Without locale set at all, blocks are kept, as most terminals
+    // are UTF-8 anyway.
+    fn detect(locale: Option<&str>) -> Glyphs {
+        match locale.map(str::to_lowercase) {
+            Some(l) if !l.contains("utf-8") && !l.contains("utf8") => Glyphs::Ascii,
+            _ => Glyphs::Blocks,
+        }
+    }
+
+    // levels of the value within a row, from empty to full.
+    pub fn levels(self) -> &'static [char] {
+        match self {
+            Glyphs::Blocks => &BLOCKS,
+            Glyphs::Ascii => &ASCII,
+        }
+    }
+
+    // Blocks fill the cell from the bottom, so with swapped colors they
+    // fill it from the top. Ascii levels are density only.
+    pub fn fill_bottom(self) -> bool {
+        self == Glyphs::Blocks
+    }
+
+    // shown at the bottom row for missing values.
+    pub fn missing(self) -> char {
+        match self {
+            Glyphs::Blocks => '.',
+            Glyphs::Ascii => '_',
+        }
+    }
+
+    // top, middle and bottom markers of the chart rows.
+    pub fn markers(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Glyphs::Blocks => ("┌", "│", "└"),
+            Glyphs::Ascii => ("+", "|", "+"),
+        }
+    }
+
+    pub fn ellipsis(self) -> char {
+        match self {
+            Glyphs::Blocks => '…',
+            Glyphs::Ascii => '~',
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GlyphsError {
+    BadGlyphs(String),
+}
+
+impl std::fmt::Display for GlyphsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            GlyphsError::BadGlyphs(ref s) => write!(f, "Unknown glyphs: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        assert_eq!(Glyphs::detect(Some("en_US.UTF-8")), Glyphs::Blocks);
+        assert_eq!(Glyphs::detect(Some("C.utf8")), Glyphs::Blocks);
+        assert_eq!(Glyphs::detect(Some("C")), Glyphs::Ascii);
+        assert_eq!(Glyphs::detect(Some("en_US.ISO-8859-1")), Glyphs::Ascii);
+        assert_eq!(Glyphs::detect(None), Glyphs::Blocks);
+        assert!(Glyphs::new("braille").is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use unicode_width::UnicodeWidthChar;
+
+// width of the title gutter in compact layout, including the space
+// separating it from the chart.
+pub const GUTTER: u16 = 20;
+
+// columns on the left of the charts: title gutter in compact layout,
+// chart markers otherwise.
+pub fn gutter_width(compact: bool) -> u16 {
+    if compact {
+        GUTTER
+    } else {
+        1
+    }
+}
+
+/// Affixes are prefix and suffix, in chars, shared by all the titles.
+/// In compact layout they are not shown, as they carry no information
+/// for telling series apart, e.g. 'cpu' in 'cpu0'..'cpu63'.
+#[derive(Debug, Default, PartialEq)]
+pub struct Affixes {
+    prefix: usize,
+    suffix: usize,
+}
+
+impl Affixes {
+    // at least one char of every title is kept.
+    pub fn common<'a>(mut titles: impl Iterator<Item = &'a str>) -> Affixes {
+        let first: Vec<char> = match titles.next() {
+            Some(t) => t.chars().collect(),
+            None => return Affixes::default(),
+        };
+        let (mut prefix, mut suffix, mut min_len) = (first.len(), first.len(), first.len());
+        let mut single = true;
+        for title in titles {
+            single = false;
+            let chars: Vec<char> = title.chars().collect();
+            prefix = first
+                .iter()
+                .zip(chars.iter())
+                .take(prefix)
+                .take_while(|(a, b)| a == b)
+                .count();
+            suffix = first
+                .iter()
+                .rev()
+                .zip(chars.iter().rev())
+                .take(suffix)
+                .take_while(|(a, b)| a == b)
+                .count();
+            min_len = min_len.min(chars.len());
+        }
+        if single {
+            return Affixes::default();
+        }
+        let prefix = prefix.min(min_len.saturating_sub(1));
+        let suffix = suffix.min(min_len.saturating_sub(prefix + 1));
+        Affixes { prefix, suffix }
+    }
+
+    pub fn strip<'a>(&self, title: &'a str) -> &'a str {
+        let len = title.chars().count();
+        let byte = |n: usize| title.char_indices().nth(n).map_or(title.len(), |(i, _)| i);
+        &title[byte(self.prefix)..byte(len.saturating_sub(self.suffix).max(self.prefix))]
+    }
+}
+
+// display width of the string in terminal cells.
+pub fn width(s: &str) -> usize {
+    s.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+// Shortens the title to fit the width, replacing the middle with
+// ellipsis. Beginning and end are usually what tells the series apart.
+pub fn elide(title: &str, max_width: usize, ellipsis: char) -> String {
+    if width(title) <= max_width {
+        return title.to_owned();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    // one cell is taken by the ellipsis.
+    let budget = max_width - 1;
+    let mut head = String::new();
+    let mut head_width = 0;
+    for c in title.chars() {
+        let w = c.width().unwrap_or(0);
+        if head_width + w > budget - budget / 2 {
+            break;
+        }
+        head.push(c);
+        head_width += w;
+    }
+    let mut tail = vec![];
+    let mut tail_width = 0;
+    for c in title.chars().rev() {
+        let w = c.width().unwrap_or(0);
+        if head_width + tail_width + w > budget {
+            break;
+        }
+        tail.push(c);
+        tail_width += w;
+    }
+    head.push(ellipsis);
+    head.extend(tail.into_iter().rev());
+    head
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn affixes() {
+        let titles = ["cpu0_busy", "cpu12_busy", "cpu3_busy"];
+        let affixes = Affixes::common(titles.iter().copied());
+        let stripped: Vec<&str> = titles.iter().map(|t| affixes.strip(t)).collect();
+        assert_eq!(stripped, vec!["0", "12", "3"]);
+
+        // title is never stripped entirely.
+        let titles = ["ab", "abc"];
+        let affixes = Affixes::common(titles.iter().copied());
+        assert_eq!(affixes.strip("ab"), "b");
+        assert_eq!(affixes.strip("abc"), "bc");
+
+        assert_eq!(Affixes::common(["cpu"].iter().copied()), Affixes::default());
+    }
+
+    #[test]
+    fn elide_by_width() {
+        assert_eq!(elide("short", 10, '…'), "short");
+        assert_eq!(elide("instructions_retired", 9, '…'), "inst…ired");
+        assert_eq!(elide("instructions_retired", 9, '~'), "inst~ired");
+        // wide chars take two cells.
+        assert_eq!(elide("日本語のタイトル", 7, '…'), "日…トル");
+        assert_eq!(elide("日本語のタイトル", 6, '…'), "日…ル");
+    }
+}
@@ -1,6 +1,12 @@
 pub mod chart;
 pub mod column;
+pub mod depth;
+pub mod diagnostics;
+pub mod glyphs;
+pub mod gutter;
+pub mod palette;
 pub mod status_bar;
 pub mod style;
 pub mod surface;
+pub mod theme;
 pub mod ui_init;
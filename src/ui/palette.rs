@@ -0,0 +1,219 @@
+use crate::ui::depth::ColorDepth;
+use crate::ui::theme::{rgb, Theme};
+
+use std::iter;
+
+use tui::style::{Color, Style};
+
+pub const MAX_BANDS: usize = 32;
+
+// bands used with truecolor when not set, as smooth gradients are cheap.
+pub const TRUECOLOR_BANDS: usize = 12;
+
+/// How negative values are drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NegativeMode {
+    // same as positive values, growing from the bottom, in negative colors.
+    Mirror,
+    // hanging from the top of the row.
+    Offset,
+}
+
+impl NegativeMode {
+    pub fn new(conf: &str) -> Result<NegativeMode, PaletteError> {
+        match conf {
+            "mirror" => Ok(NegativeMode::Mirror),
+            "offset" => Ok(NegativeMode::Offset),
+            _ => Err(PaletteError::BadNegativeMode(conf.to_owned())),
+        }
+    }
+}
+
+/// Palette holds colors for positive and negative values, starting
+/// with the background. Each pair of adjacent colors is a band.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub positive: Vec<Color>,
+    pub negative: Vec<Color>,
+    pub negative_mode: NegativeMode,
+    // style of the cells without value.
+    pub blank: Style,
+    pub depth: ColorDepth,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::new(
+            &Theme::default(),
+            None,
+            NegativeMode::Offset,
+            ColorDepth::Colors256,
+        )
+        .unwrap()
+    }
+}
+
+fn to_linear(c: u8) -> f64 {
+    let c = c as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(c: f64) -> u8 {
+    let c = if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Colors are mixed in OKLab space, where equal steps look like equal
+// changes in lightness and hue, see https://bottosson.github.io/posts/oklab/
+fn to_oklab((r, g, b): (u8, u8, u8)) -> [f64; 3] {
+    let (r, g, b) = (to_linear(r), to_linear(g), to_linear(b));
+    let l = (0.412_221_470_8 * r + 0.536_332_536_3 * g + 0.051_445_992_9 * b).cbrt();
+    let m = (0.211_903_498_2 * r + 0.680_699_545_1 * g + 0.107_396_956_6 * b).cbrt();
+    let s = (0.088_302_461_9 * r + 0.281_718_837_6 * g + 0.629_978_700_5 * b).cbrt();
+    [
+        0.210_454_255_3 * l + 0.793_617_785 * m - 0.004_072_046_8 * s,
+        1.977_998_495_1 * l - 2.428_592_205 * m + 0.450_593_709_9 * s,
+        0.025_904_037_1 * l + 0.782_771_766_2 * m - 0.808_675_766 * s,
+    ]
+}
+
+fn from_oklab([l, a, b]: [f64; 3]) -> (u8, u8, u8) {
+    let l_ = (l + 0.396_337_777_4 * a + 0.215_803_757_3 * b).powi(3);
+    let m_ = (l - 0.105_561_345_8 * a - 0.063_854_172_8 * b).powi(3);
+    let s_ = (l - 0.089_484_177_5 * a - 1.291_485_548 * b).powi(3);
+    (
+        from_linear(4.076_741_662_1 * l_ - 3.307_711_591_3 * m_ + 0.230_969_929_2 * s_),
+        from_linear(-1.268_438_004_6 * l_ + 2.609_757_401_1 * m_ - 0.341_319_396_5 * s_),
+        from_linear(-0.004_196_086_3 * l_ - 0.703_418_614_7 * m_ + 1.707_614_701 * s_),
+    )
+}
+
+// Background followed by a color for each band, evenly spaced in
+// OKLab between two anchors: the lowest and the most intense colors
+// of the palette. Colors in between only matter for the default
+// six-step palettes.
+fn generate(palette: &[Color], bands: usize, depth: ColorDepth) -> Vec<Color> {
+    let (low, high) = (
+        to_oklab(rgb(palette[1])),
+        to_oklab(rgb(palette[palette.len() - 1])),
+    );
+    let colors = (1..=bands).map(|i| {
+        let t = match bands {
+            1 => 1.0,
+            _ => (i - 1) as f64 / (bands - 1) as f64,
+        };
+        let mixed = [0, 1, 2].map(|c| low[c] + (high[c] - low[c]) * t);
+        let (r, g, b) = from_oklab(mixed);
+        depth.quantize(Color::Rgb(r, g, b))
+    });
+    let (r, g, b) = rgb(palette[0]);
+    iter::once(depth.quantize(Color::Rgb(r, g, b)))
+        .chain(colors)
+        .collect()
+}
+
+impl Palette {
+    // Theme is expected to be converted for the depth already, see
+    // ColorDepth::theme(). Without band count, theme palettes are used
+    // as they are, except for truecolor. With 16 colors or less there
+    // are too few colors to pick from, and band count is ignored.
+    pub fn new(
+        theme: &Theme,
+        bands: Option<usize>,
+        negative_mode: NegativeMode,
+        depth: ColorDepth,
+    ) -> Result<Palette, PaletteError> {
+        let bands = match (bands, depth) {
+            (Some(n), _) if n == 0 || n > MAX_BANDS => return Err(PaletteError::BadBands(n)),
+            (_, ColorDepth::Colors16) | (_, ColorDepth::Mono) => None,
+            (None, ColorDepth::TrueColor) => Some(TRUECOLOR_BANDS),
+            (bands, _) => bands,
+        };
+        let (positive, negative) = match bands {
+            None => (theme.positive.clone(), theme.negative.clone()),
+            Some(n) => (
+                generate(&theme.positive, n, depth),
+                generate(&theme.negative, n, depth),
+            ),
+        };
+        // without colors, sign is only told by the direction.
+        let negative_mode = match depth {
+            ColorDepth::Mono => NegativeMode::Offset,
+            _ => negative_mode,
+        };
+        Ok(Palette {
+            positive,
+            negative,
+            negative_mode,
+            blank: theme.text(),
+            depth,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum PaletteError {
+    BadBands(usize),
+    BadNegativeMode(String),
+}
+
+impl std::fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            PaletteError::BadBands(n) => {
+                write!(f, "Bad number of bands: {}, must be 1..{}", n, MAX_BANDS)
+            }
+            PaletteError::BadNegativeMode(ref s) => write!(f, "Unknown negative mode: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated() {
+        let theme = Theme::default();
+        let palette =
+            Palette::new(&theme, Some(5), NegativeMode::Mirror, ColorDepth::Colors256).unwrap();
+        // bands go from the lowest to the most intense theme color.
+        assert_eq!(palette.positive.len(), 6);
+        assert_eq!(palette.positive[..2], theme.positive[..2]);
+        assert_eq!(palette.positive[5], theme.positive[5]);
+        assert_eq!(palette.negative[1], theme.negative[1]);
+        assert_eq!(palette.negative[5], theme.negative[5]);
+
+        // default is the six-step theme palette.
+        let palette =
+            Palette::new(&theme, None, NegativeMode::Mirror, ColorDepth::Colors256).unwrap();
+        assert_eq!(palette.positive, theme.positive);
+
+        let palette =
+            Palette::new(&theme, None, NegativeMode::Mirror, ColorDepth::TrueColor).unwrap();
+        assert_eq!(palette.positive.len(), TRUECOLOR_BANDS + 1);
+        assert_eq!(palette.positive[0], Color::Rgb(255, 255, 255));
+        assert_eq!(palette.positive[1], Color::Rgb(215, 255, 215));
+        assert_eq!(palette.positive[TRUECOLOR_BANDS], Color::Rgb(0, 95, 0));
+
+        let bands = |depth| Palette::new(&theme, Some(8), NegativeMode::Offset, depth);
+        assert_eq!(bands(ColorDepth::Colors16).unwrap().positive.len(), 6);
+        assert_eq!(
+            bands(ColorDepth::Mono).unwrap().negative_mode,
+            NegativeMode::Offset
+        );
+        assert!(bands(ColorDepth::TrueColor).is_ok());
+        assert!(
+            Palette::new(&theme, Some(0), NegativeMode::Offset, ColorDepth::TrueColor).is_err()
+        );
+        assert!(NegativeMode::new("flip").is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::data::state::State;
-use crate::ui::style::{default, EmptyBox};
+use crate::ui::style::EmptyBox;
+use crate::ui::theme::Theme;
 
 use tui::buffer::Buffer;
 use tui::layout::Rect;
@@ -10,13 +11,19 @@ use tui::widgets::Widget;
 /// And cuurrent 'epoch'.
 pub struct StatusBar<'a> {
     state: &'a State,
+    theme: &'a Theme,
     series_displayed: (usize, usize),
 }
 
 impl<'a> StatusBar<'a> {
-    pub fn new(state: &'a State, series_displayed: (usize, usize)) -> StatusBar<'a> {
+    pub fn new(
+        state: &'a State,
+        theme: &'a Theme,
+        series_displayed: (usize, usize),
+    ) -> StatusBar<'a> {
         StatusBar {
             state,
+            theme,
             series_displayed,
         }
     }
@@ -24,19 +31,40 @@ impl<'a> StatusBar<'a> {
 
 impl<'a> Widget for StatusBar<'a> {
     fn draw(&mut self, area: Rect, buf: &mut Buffer) {
-        EmptyBox::fill(area, buf);
+        EmptyBox::fill(area, buf, self.theme.text());
 
-        let message = match (self.state.error_message.as_ref(), self.state.is_auto()) {
+        let mut message = match (self.state.error_message.as_ref(), self.state.is_auto()) {
             (Some(err), _) => format!("error: {}", err),
-            (None, false) => format!("paused"),
-            (None, true) => format!("reading"),
+            (None, false) if self.state.new_columns > 0 => {
+                format!("paused, +{} new columns", self.state.new_columns)
+            }
+            (None, false) => "paused".to_string(),
+            (None, true) => "reading".to_string(),
         };
+        let diagnostics = &self.state.diagnostics;
+        if let Some(warning) = diagnostics.warning.as_ref() {
+            message = format!("{}; {}", message, warning);
+        }
+        if diagnostics.high_rate {
+            message = format!(
+                "{}; many lines rejected ({} of {}), 'e' to inspect",
+                message, diagnostics.rejected, diagnostics.lines
+            );
+        }
+
+        if self.state.dropped > 0 {
+            message = format!("{}; {} columns dropped", message, self.state.dropped);
+        } else if self.state.merged > 0 {
+            message = format!("{}; {} columns merged", message, self.state.merged);
+        } else if self.state.lagging {
+            message = format!("{}; lagging behind input", message);
+        }
 
         buf.set_string(
             area.left(),
             area.top(),
             &message,
-            default().modifier(Modifier::REVERSED),
+            self.theme.text().modifier(Modifier::REVERSED),
         );
 
         // series format on the right
@@ -50,6 +78,11 @@ impl<'a> Widget for StatusBar<'a> {
         } else {
             "no data".to_string()
         };
-        buf.set_string(area.right() - y.len() as u16, area.top(), &y, default());
+        buf.set_string(
+            area.right() - y.len() as u16,
+            area.top(),
+            &y,
+            self.theme.text(),
+        );
     }
 }
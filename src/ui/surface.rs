@@ -1,7 +1,12 @@
+use crate::app::window::fit;
 use crate::data::state::State;
 use crate::ui::chart::Charts;
+use crate::ui::diagnostics::DiagnosticsPane;
+use crate::ui::glyphs::Glyphs;
+use crate::ui::palette::Palette;
 use crate::ui::status_bar::StatusBar;
 use crate::ui::style::EmptyBox;
+use crate::ui::theme::Theme;
 
 use failure::Error;
 
@@ -10,9 +15,13 @@ use tui::layout::{Constraint, Direction, Layout};
 use tui::widgets::Widget;
 
 pub trait Surface {
-    fn height(&self) -> Result<i64, Error>;
-    fn width(&self) -> Result<i64, Error>;
+    // lines available for the series.
+    fn lines(&self) -> Result<i64, Error>;
+    // terminal width, including the space used for titles.
+    fn columns(&self) -> Result<i64, Error>;
     fn render(&mut self, state: &State) -> Result<(), Error>;
+    // forgets what's on screen, so that next render redraws everything.
+    fn clear(&mut self) -> Result<(), Error>;
 }
 
 // drawing surface; is aware of terminal size and layout
@@ -21,14 +30,53 @@ where
     B: Backend,
 {
     terminal: &'a mut tui::Terminal<B>,
+    theme: Theme,
+    palette: Palette,
+    glyphs: Glyphs,
 }
 
 impl<'a, B> TermSurface<'a, B>
 where
     B: Backend,
 {
-    pub fn new(terminal: &'a mut tui::Terminal<B>) -> Self {
-        TermSurface { terminal }
+    pub fn new(
+        terminal: &'a mut tui::Terminal<B>,
+        theme: Theme,
+        palette: Palette,
+        glyphs: Glyphs,
+    ) -> Self {
+        TermSurface {
+            terminal,
+            theme,
+            palette,
+            glyphs,
+        }
+    }
+}
+
+impl<'a, B> TermSurface<'a, B>
+where
+    B: Backend,
+{
+    // diagnostics pane takes the whole screen except status bar.
+    fn render_diagnostics(
+        &mut self,
+        state: &State,
+        series_displayed: (usize, usize),
+    ) -> Result<(), Error> {
+        let constraints = vec![Constraint::Min(0), Constraint::Length(1)];
+        let theme = &self.theme;
+        let mut status_bar = StatusBar::new(state, theme, series_displayed);
+        self.terminal.draw(|mut f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(constraints)
+                .split(f.size());
+
+            DiagnosticsPane::new(&state.diagnostics, theme).render(&mut f, chunks[0]);
+            status_bar.render(&mut f, chunks[1]);
+        })?;
+        Ok(())
     }
 }
 
@@ -36,48 +84,51 @@ impl<'a, B> Surface for TermSurface<'a, B>
 where
     B: Backend,
 {
-    // height in series. How many series will fit?
-    fn height(&self) -> Result<i64, Error> {
-        Ok((self.terminal.size()?.height as i64 - 2) / Charts::SERIES_HEIGHT)
+    // everything except x axis and status bar.
+    fn lines(&self) -> Result<i64, Error> {
+        Ok((self.terminal.size()?.height as i64 - 2).max(0))
+    }
+
+    fn columns(&self) -> Result<i64, Error> {
+        Ok(self.terminal.size()?.width as i64)
     }
 
-    fn width(&self) -> Result<i64, Error> {
-        Ok(self.terminal.size()?.width as i64 - 1)
+    fn clear(&mut self) -> Result<(), Error> {
+        self.terminal.clear()?;
+        Ok(())
     }
 
     fn render(&mut self, state: &State) -> Result<(), Error> {
-        let data = &state.data;
-        let mut data = &data.y[state.y.offset as usize..data.y.len()];
+        let heights = state.heights();
+        let heights = &heights[state.y.offset as usize..];
+        let shown = fit(heights.iter(), self.lines()?) as usize;
 
-        let h = self.height()? as usize;
-        if h < data.len() {
-            data = &data[0..h];
+        let series_displayed = (state.y.offset as usize, state.y.offset as usize + shown);
+        if state.diagnostics_shown() {
+            return self.render_diagnostics(state, series_displayed);
         }
 
-        let mut status_bar = StatusBar::new(
-            state,
-            (
-                state.y.offset as usize,
-                state.y.offset as usize + data.len(),
-            ),
-        );
+        let constraints = vec![
+            // x axis + all series
+            Constraint::Length(1 + heights[..shown].iter().sum::<i64>() as u16),
+            Constraint::Min(0),
+            Constraint::Length(1), // status bar
+        ];
 
-        let mut constraints = vec![];
-        // x axis + all series
-        constraints.push(Constraint::Length(
-            1 + Charts::SERIES_HEIGHT as u16 * data.len() as u16,
-        ));
-        constraints.push(Constraint::Min(0));
-        constraints.push(Constraint::Length(1)); // status bar
+        let (theme, palette, glyphs) = (&self.theme, &self.palette, self.glyphs);
+        let mut status_bar = StatusBar::new(state, theme, series_displayed);
         self.terminal.draw(|mut f| {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints(constraints)
                 .split(f.size());
 
-            Charts::new(&state).render(&mut f, chunks[0]);
+            Charts::new(state, theme, palette, glyphs).render(&mut f, chunks[0]);
             status_bar.render(&mut f, chunks[2]);
-            EmptyBox {}.render(&mut f, chunks[1]);
+            EmptyBox {
+                style: theme.text(),
+            }
+            .render(&mut f, chunks[1]);
         })?;
         Ok(())
     }
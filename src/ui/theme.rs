@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use tui::style::{Color, Style};
+
+/// Theme defines colors of everything on the screen. Palettes start
+/// with the background color and go to the most intense one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub background: Color,
+    pub text: Color,
+    pub cursor: Color,
+    pub positive: Vec<Color>,
+    pub negative: Vec<Color>,
+}
+
+fn indexed(colors: &[u8]) -> Vec<Color> {
+    colors.iter().map(|c| Color::Indexed(*c)).collect()
+}
+
+impl Theme {
+    pub fn light() -> Theme {
+        Theme {
+            background: Color::Indexed(231),
+            text: Color::Black,
+            cursor: Color::Black,
+            positive: indexed(&[231, 194, 150, 107, 64, 22]),
+            negative: indexed(&[231, 224, 181, 131, 88, 52]),
+        }
+    }
+
+    // for dark terminals; more intense colors are brighter.
+    pub fn dark() -> Theme {
+        Theme {
+            background: Color::Indexed(234),
+            text: Color::Indexed(252),
+            cursor: Color::Indexed(229),
+            positive: indexed(&[234, 22, 28, 34, 40, 46]),
+            negative: indexed(&[234, 52, 88, 124, 160, 196]),
+        }
+    }
+
+    // blue and orange are told apart with any kind of color blindness.
+    pub fn colorblind() -> Theme {
+        Theme {
+            positive: indexed(&[231, 153, 111, 75, 33, 19]),
+            negative: indexed(&[231, 223, 216, 215, 208, 130]),
+            ..Theme::light()
+        }
+    }
+
+    // negative values only differ by direction, see NegativeMode.
+    pub fn grayscale() -> Theme {
+        Theme {
+            positive: indexed(&[231, 252, 248, 244, 240, 236]),
+            negative: indexed(&[231, 252, 248, 244, 240, 236]),
+            ..Theme::light()
+        }
+    }
+
+    // style of the text and empty space.
+    pub fn text(&self) -> Style {
+        Style::default().bg(self.background).fg(self.text)
+    }
+
+    pub fn cursor(&self) -> Style {
+        Style::default().bg(self.background).fg(self.cursor)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::light()
+    }
+}
+
+// Approximate RGB value of the color, for palette generation.
+// Named colors are only used for text, and are not converted.
+pub fn rgb(color: Color) -> (u8, u8, u8) {
+    const SYSTEM: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (128, 0, 0),
+        (0, 128, 0),
+        (128, 128, 0),
+        (0, 0, 128),
+        (128, 0, 128),
+        (0, 128, 128),
+        (192, 192, 192),
+        (128, 128, 128),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (0, 0, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(i) if i < 16 => SYSTEM[i as usize],
+        Color::Indexed(i) if i < 232 => {
+            let i = (i - 16) as usize;
+            (LEVELS[i / 36], LEVELS[i / 6 % 6], LEVELS[i % 6])
+        }
+        Color::Indexed(i) => {
+            let v = 8 + 10 * (i - 232);
+            (v, v, v)
+        }
+        _ => (0, 0, 0),
+    }
+}
+
+// color is either 256-color palette index, or #rrggbb.
+fn parse_color(s: &str) -> Result<Color, ThemeError> {
+    let bad = || ThemeError::BadColor(s.to_owned());
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 {
+            return Err(bad());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| bad());
+        return Ok(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    s.parse::<u8>().map(Color::Indexed).map_err(|_| bad())
+}
+
+fn parse_palette(s: &str) -> Result<Vec<Color>, ThemeError> {
+    let palette = s
+        .split(',')
+        .map(|c| parse_color(c.trim()))
+        .collect::<Result<Vec<Color>, ThemeError>>()?;
+    if palette.len() < 2 {
+        return Err(ThemeError::BadPalette(s.to_owned()));
+    }
+    Ok(palette)
+}
+
+// default location of user themes.
+fn themes_file() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config| config.join("hcl").join("themes"))
+}
+
+/// Themes are built-in themes and the ones loaded from the config file.
+pub struct Themes {
+    themes: HashMap<String, Theme>,
+}
+
+impl Themes {
+    pub fn new() -> Themes {
+        let themes = vec![
+            ("light", Theme::light()),
+            ("dark", Theme::dark()),
+            ("colorblind", Theme::colorblind()),
+            ("grayscale", Theme::grayscale()),
+        ];
+        Themes {
+            themes: themes
+                .into_iter()
+                .map(|(name, theme)| (name.to_owned(), theme))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Theme, ThemeError> {
+        self.themes
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ThemeError::Unknown(name.to_owned()))
+    }
+
+    // Loads user themes. Default file is only read if it exists.
+    pub fn load_file(&mut self, file: Option<&str>) -> Result<(), ThemeError> {
+        let (path, required) = match file.map(PathBuf::from) {
+            Some(path) => (path, true),
+            None => match themes_file() {
+                Some(path) => (path, false),
+                None => return Ok(()),
+            },
+        };
+        let name = path.display().to_string();
+        match fs::read_to_string(&path) {
+            Ok(conf) => self
+                .load(&conf)
+                .map_err(|e| ThemeError::File(name, Box::new(e))),
+            Err(e) if !required && e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(ThemeError::Io(name, e)),
+        }
+    }
+
+    // Reads themes in the following format:
+    //   [name]
+    //   background = 231
+    //   text = #000000
+    //   positive = 231,194,150,107,64,22
+    // Colors which are not set are taken from the theme with the same
+    // name, so that built-in themes can be adjusted, or from light theme.
+    pub fn load(&mut self, conf: &str) -> Result<(), ThemeError> {
+        let mut current: Option<(String, Theme)> = None;
+        for (i, line) in conf.lines().enumerate() {
+            let at = |e: ThemeError| ThemeError::Line(i + 1, Box::new(e));
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                if let Some((name, theme)) = current.take() {
+                    self.themes.insert(name, theme);
+                }
+                let name = name.trim().to_owned();
+                let theme = self.get(&name).unwrap_or_default();
+                current = Some((name, theme));
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(at(ThemeError::BadFormat(line.to_owned()))),
+            };
+            let theme = match current.as_mut() {
+                Some((_, theme)) => theme,
+                None => return Err(at(ThemeError::BadFormat(line.to_owned()))),
+            };
+            match key {
+                "background" => theme.background = parse_color(value).map_err(at)?,
+                "text" => theme.text = parse_color(value).map_err(at)?,
+                "cursor" => theme.cursor = parse_color(value).map_err(at)?,
+                "positive" => theme.positive = parse_palette(value).map_err(at)?,
+                "negative" => theme.negative = parse_palette(value).map_err(at)?,
+                _ => return Err(at(ThemeError::BadFormat(line.to_owned()))),
+            }
+        }
+        if let Some((name, theme)) = current {
+            self.themes.insert(name, theme);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Unknown(String),
+    BadColor(String),
+    BadPalette(String),
+    BadFormat(String),
+    Line(usize, Box<ThemeError>),
+    File(String, Box<ThemeError>),
+    Io(String, io::Error),
+}
+
+impl std::fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ThemeError::Unknown(ref s) => write!(f, "Unknown theme: {}", s),
+            ThemeError::BadColor(ref s) => write!(f, "Bad color: {}", s),
+            ThemeError::BadPalette(ref s) => {
+                write!(f, "Palette needs at least 2 colors: {}", s)
+            }
+            ThemeError::BadFormat(ref s) => write!(f, "Bad Format: {}", s),
+            ThemeError::Line(n, ref e) => write!(f, "line {}: {}", n, e),
+            ThemeError::File(ref path, ref e) => write!(f, "{}: {}", path, e),
+            ThemeError::Io(ref path, ref e) => write!(f, "{}: {}", path, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_themes() {
+        let mut themes = Themes::new();
+        themes
+            .load(
+                "# solarized-ish
+[solar]
+background = #fdf6e3
+positive = 230, 187, 143, 100, 58
+
+[dark]
+cursor = 196
+",
+            )
+            .unwrap();
+        let solar = themes.get("solar").unwrap();
+        assert_eq!(solar.background, Color::Rgb(0xfd, 0xf6, 0xe3));
+        assert_eq!(solar.positive.len(), 5);
+        assert_eq!(solar.negative, Theme::light().negative);
+        // built-in themes can be adjusted.
+        let dark = themes.get("dark").unwrap();
+        assert_eq!(dark.cursor, Color::Indexed(196));
+        assert_eq!(dark.background, Theme::dark().background);
+        assert!(themes.get("solarized").is_err());
+
+        let err = Themes::new().load("[a]\ntext = 256\n").unwrap_err();
+        assert_eq!(format!("{}", err), "line 2: Bad color: 256");
+        assert!(Themes::new().load("text = 1").is_err());
+        assert!(Themes::new().load("[a]\npositive = 1").is_err());
+    }
+
+    #[test]
+    fn rgb_values() {
+        assert_eq!(rgb(Color::Indexed(231)), (255, 255, 255));
+        assert_eq!(rgb(Color::Indexed(22)), (0, 95, 0));
+        assert_eq!(rgb(Color::Indexed(232)), (8, 8, 8));
+        assert_eq!(rgb(Color::Indexed(9)), (255, 0, 0));
+    }
+}
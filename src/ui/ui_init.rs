@@ -1,19 +1,79 @@
-use std::io::stdout;
+use crate::ui::depth::{BasicColors, ColorDepth};
+
+use std::io::{self, stdout, Write};
 use termion::input::MouseTerminal;
 use termion::raw::IntoRawMode;
 use termion::screen::AlternateScreen;
 use tui::backend::{Backend, TermionBackend};
 use tui::Terminal;
 
+// same mouse reporting modes MouseTerminal enables.
+const ENTER_MOUSE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+const EXIT_MOUSE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+
+fn get_attr() -> io::Result<libc::termios> {
+    let mut attr: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &mut attr) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(attr)
+}
+
+fn set_attr(attr: &libc::termios) -> io::Result<()> {
+    if unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, attr) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// TermModes are terminal attributes before and after switching to raw
+/// mode, so that the terminal can be given back on suspend and taken
+/// again on resume. On exit, terminal is restored by dropping it.
+pub struct TermModes {
+    cooked: libc::termios,
+    raw: libc::termios,
+}
+
+impl TermModes {
+    pub fn suspend(&self) -> io::Result<()> {
+        let mut out = stdout();
+        write!(
+            out,
+            "{}{}{}",
+            EXIT_MOUSE,
+            termion::screen::ToMainScreen,
+            termion::cursor::Show
+        )?;
+        out.flush()?;
+        set_attr(&self.cooked)
+    }
+
+    pub fn resume(&self) -> io::Result<()> {
+        set_attr(&self.raw)?;
+        let mut out = stdout();
+        write!(
+            out,
+            "{}{}{}",
+            termion::screen::ToAlternateScreen,
+            ENTER_MOUSE,
+            termion::cursor::Hide
+        )?;
+        out.flush()
+    }
+}
+
 // Init and return terminal
-pub fn init() -> Result<tui::Terminal<impl Backend>, failure::Error> {
+pub fn init(depth: ColorDepth) -> Result<(tui::Terminal<impl Backend>, TermModes), failure::Error> {
+    let cooked = get_attr()?;
     let stdout = stdout().into_raw_mode()?;
+    let raw = get_attr()?;
     let stdout = MouseTerminal::from(stdout);
     let stdout = AlternateScreen::from(stdout);
+    let stdout = BasicColors::new(stdout, depth);
     let backend = TermionBackend::new(stdout);
 
     let mut terminal = Terminal::new(backend)?;
     terminal.hide_cursor()?;
 
-    Ok(terminal)
+    Ok((terminal, TermModes { cooked, raw }))
 }